@@ -0,0 +1,312 @@
+pub mod printer;
+pub mod visit;
+
+use crate::intern::Symbol;
+use crate::span::Span;
+
+#[derive(Debug, Clone, PartialEq)]
+// a whole source file: "source" id {funcdef} body
+pub struct Program {
+    pub name: Ident,
+    pub functions: Vec<FunctionDecl>,
+    pub body: Body,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+// a name together with where it was written
+pub struct Ident {
+    pub symbol: Symbol,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+// "function" id "(" [param {"," param}] ")" ["to" type] body
+pub struct FunctionDecl {
+    // the text of the doc comment right before the function, if any
+    pub doc: Option<Symbol>,
+
+    pub name: Ident,
+    pub params: Vec<Param>,
+
+    // None for a procedure, which returns nothing
+    pub return_type: Option<Type>,
+
+    pub body: Body,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+// a parameter of a function: type id
+pub struct Param {
+    pub ty: Type,
+    pub name: Ident,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+// "begin" {vardef} statements "end"
+pub struct Body {
+    pub vars: Vec<VarDecl>,
+
+    // the statements of the body, a single Stmt::Relax if it does nothing
+    pub statements: Vec<Stmt>,
+
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+// the types a variable, parameter or function result can have
+pub enum BaseType {
+    Boolean,
+    Integer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+// ("boolean" | "integer") ["array"]
+pub struct Type {
+    pub base: BaseType,
+    pub is_array: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+// type id {"," id} ";"
+pub struct VarDecl {
+    pub ty: Type,
+    pub names: Vec<Ident>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    // id ["[" simple "]"] ":=" (expr | "array" simple)
+    Assign {
+        target: Ident,
+        index: Option<Expr>,
+        value: AssignValue,
+        span: Span,
+    },
+    // "call" id "(" [expr {"," expr}] ")"
+    Call {
+        name: Ident,
+        args: Vec<Expr>,
+        span: Span,
+    },
+    // "if" expr "then" statements {"elsif" expr "then" statements}
+    // ["else" statements] "end"
+    If {
+        branches: Vec<IfBranch>,
+        else_branch: Option<Vec<Stmt>>,
+        span: Span,
+    },
+    // "get" id ["[" simple "]"]
+    Get {
+        target: Ident,
+        index: Option<Expr>,
+        span: Span,
+    },
+    // "leave" [expr]
+    Leave {
+        value: Option<Expr>,
+        span: Span,
+    },
+    // "put" expr, the items to write being joined by concatenation
+    Put {
+        value: Expr,
+        span: Span,
+    },
+    // "relax", which does nothing
+    Relax {
+        span: Span,
+    },
+    // "while" expr "do" statements "end"
+    While {
+        condition: Expr,
+        body: Vec<Stmt>,
+        span: Span,
+    },
+    // a statement with a syntax error, covering the tokens skipped to recover
+    // from it
+    Error {
+        span: Span,
+    },
+}
+
+impl Stmt {
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Assign { span, .. }
+            | Stmt::Call { span, .. }
+            | Stmt::If { span, .. }
+            | Stmt::Get { span, .. }
+            | Stmt::Leave { span, .. }
+            | Stmt::Put { span, .. }
+            | Stmt::Relax { span }
+            | Stmt::While { span, .. }
+            | Stmt::Error { span } => *span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+// what is assigned to a variable
+pub enum AssignValue {
+    Expr(Expr),
+    // "array" simple, a new array of the given length
+    Array { length: Expr, span: Span },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+// the condition of an "if" or "elsif" and the statements it guards
+pub struct IfBranch {
+    pub condition: Expr,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    // "-"
+    Negate,
+    // "not"
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    // relational operators
+    Equal,
+    GreaterEqual,
+    GreaterThan,
+    LessEqual,
+    LessThan,
+    NotEqual,
+
+    // additive operators
+    Subtract,
+    Or,
+    Add,
+
+    // multiplicative operators
+    And,
+    Divide,
+    Multiply,
+    Remainder,
+
+    // ".", which joins the items of a "put" statement
+    Concatenate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+// how tightly an operator binds, from loosest to tightest
+pub enum Precedence {
+    Concatenation,
+    Relational,
+    Additive,
+    Multiplicative,
+    // "not" and the operands of operators, which bind tighter than any binary
+    // operator
+    Factor,
+}
+
+impl Precedence {
+    // the next tighter precedence
+    pub fn tighter(self) -> Precedence {
+        match self {
+            Precedence::Concatenation => Precedence::Relational,
+            Precedence::Relational => Precedence::Additive,
+            Precedence::Additive => Precedence::Multiplicative,
+            Precedence::Multiplicative | Precedence::Factor => Precedence::Factor,
+        }
+    }
+}
+
+impl BinaryOp {
+    pub fn precedence(self) -> Precedence {
+        match self {
+            BinaryOp::Concatenate => Precedence::Concatenation,
+            BinaryOp::Equal
+            | BinaryOp::GreaterEqual
+            | BinaryOp::GreaterThan
+            | BinaryOp::LessEqual
+            | BinaryOp::LessThan
+            | BinaryOp::NotEqual => Precedence::Relational,
+            BinaryOp::Subtract | BinaryOp::Or | BinaryOp::Add => Precedence::Additive,
+            BinaryOp::And | BinaryOp::Divide | BinaryOp::Multiply | BinaryOp::Remainder => {
+                Precedence::Multiplicative
+            }
+        }
+    }
+
+    // whether the operator may be chained, grouping to the left. Relational
+    // operators are non-associative, so "a < b < c" is an error.
+    pub fn is_associative(self) -> bool {
+        self.precedence() != Precedence::Relational
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    // a variable
+    Variable(Ident),
+    // a number literal, which is never negative
+    Number {
+        value: u64,
+        span: Span,
+    },
+    // "true" or "false"
+    Boolean {
+        value: bool,
+        span: Span,
+    },
+    // a string literal, only allowed as an item of a "put" statement
+    String {
+        value: Symbol,
+        span: Span,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expr>,
+        span: Span,
+    },
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+        span: Span,
+    },
+    // id "[" simple "]"
+    Index {
+        array: Ident,
+        index: Box<Expr>,
+        span: Span,
+    },
+    // id "(" [expr {"," expr}] ")"
+    Call {
+        name: Ident,
+        args: Vec<Expr>,
+        span: Span,
+    },
+    // an expression with a syntax error, covering the token skipped to recover
+    // from it, if any
+    Error {
+        span: Span,
+    },
+}
+
+impl Expr {
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Variable(ident) => ident.span,
+            Expr::Number { span, .. }
+            | Expr::Boolean { span, .. }
+            | Expr::String { span, .. }
+            | Expr::Unary { span, .. }
+            | Expr::Binary { span, .. }
+            | Expr::Index { span, .. }
+            | Expr::Call { span, .. }
+            | Expr::Error { span } => *span,
+        }
+    }
+}
@@ -0,0 +1,481 @@
+use super::{
+    AssignValue, BaseType, BinaryOp, Body, Expr, FunctionDecl, Ident, Precedence, Program, Stmt,
+    Type, UnaryOp, VarDecl,
+};
+use crate::intern::Interner;
+use crate::token::TokenKind;
+
+// the number of spaces each level of nesting is indented by
+const INDENT: usize = 2;
+
+// writes a program back out as canonical SVLang source, with one statement or
+// declaration per line and only the parentheses the precedence of the
+// operators requires. Parsing the result gives the same AST, spans aside.
+//
+// Error nodes, which only come from parsing in recovery mode, are written as
+// "<error>", so the source of a program with syntax errors is not valid.
+pub fn print_program(program: &Program, interner: &Interner) -> String {
+    let mut printer = Printer::new(interner);
+    printer.program(program);
+    printer.out
+}
+
+// writes an expression as SVLang source, see `print_program`
+pub fn print_expr(expr: &Expr, interner: &Interner) -> String {
+    let mut printer = Printer::new(interner);
+    printer.expr(expr, Precedence::Concatenation);
+    printer.out
+}
+
+struct Printer<'i> {
+    interner: &'i Interner,
+    out: String,
+
+    // the nesting level of the line being written
+    indent: usize,
+}
+
+impl<'i> Printer<'i> {
+    fn new(interner: &'i Interner) -> Self {
+        Printer {
+            interner,
+            out: String::new(),
+            indent: 0,
+        }
+    }
+
+    fn program(&mut self, program: &Program) {
+        self.line("source ");
+        self.ident(program.name);
+        for function in &program.functions {
+            self.function(function);
+        }
+        self.body(&program.body);
+        self.out.push('\n');
+    }
+
+    fn function(&mut self, function: &FunctionDecl) {
+        if let Some(doc) = function.doc {
+            self.line("{! ");
+            self.out.push_str(self.interner.resolve(doc));
+            self.out.push_str(" }");
+        }
+        self.line("function ");
+        self.ident(function.name);
+        self.out.push('(');
+        for (i, param) in function.params.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.ty(param.ty);
+            self.out.push(' ');
+            self.ident(param.name);
+        }
+        self.out.push(')');
+        if let Some(return_type) = function.return_type {
+            self.out.push_str(" to ");
+            self.ty(return_type);
+        }
+        self.body(&function.body);
+    }
+
+    fn body(&mut self, body: &Body) {
+        self.line("begin");
+        self.indent += 1;
+        for var in &body.vars {
+            self.var_decl(var);
+        }
+        self.statements(&body.statements);
+        self.indent -= 1;
+        self.line("end");
+    }
+
+    fn var_decl(&mut self, var: &VarDecl) {
+        self.line("");
+        self.ty(var.ty);
+        self.out.push(' ');
+        self.idents(&var.names);
+        self.out.push(';');
+    }
+
+    fn ty(&mut self, ty: Type) {
+        self.out.push_str(match ty.base {
+            BaseType::Boolean => "boolean",
+            BaseType::Integer => "integer",
+        });
+        if ty.is_array {
+            self.out.push_str(" array");
+        }
+    }
+
+    // the statements of a body or branch, one per line at the current
+    // indentation
+    fn statements(&mut self, statements: &[Stmt]) {
+        for (i, stmt) in statements.iter().enumerate() {
+            if i > 0 {
+                self.out.push(';');
+            }
+            self.stmt(stmt);
+        }
+    }
+
+    // writes the statements of a branch one level deeper
+    fn block(&mut self, statements: &[Stmt]) {
+        self.indent += 1;
+        self.statements(statements);
+        self.indent -= 1;
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Assign {
+                target,
+                index,
+                value,
+                ..
+            } => {
+                self.line("");
+                self.ident(*target);
+                if let Some(index) = index {
+                    self.index(index);
+                }
+                self.out.push_str(" := ");
+                match value {
+                    AssignValue::Expr(expr) => self.expr(expr, Precedence::Concatenation),
+                    AssignValue::Array { length, .. } => {
+                        self.out.push_str("array ");
+                        self.expr(length, Precedence::Additive);
+                    }
+                }
+            }
+            Stmt::Call { name, args, .. } => {
+                self.line("call ");
+                self.ident(*name);
+                self.args(args);
+            }
+            Stmt::If {
+                branches,
+                else_branch,
+                ..
+            } => {
+                for (i, branch) in branches.iter().enumerate() {
+                    self.line(if i == 0 { "if " } else { "elsif " });
+                    self.expr(&branch.condition, Precedence::Concatenation);
+                    self.out.push_str(" then");
+                    self.block(&branch.body);
+                }
+                if let Some(else_branch) = else_branch {
+                    self.line("else");
+                    self.block(else_branch);
+                }
+                self.line("end");
+            }
+            Stmt::Get { target, index, .. } => {
+                self.line("get ");
+                self.ident(*target);
+                if let Some(index) = index {
+                    self.index(index);
+                }
+            }
+            Stmt::Leave { value, .. } => {
+                self.line("leave");
+                if let Some(value) = value {
+                    self.out.push(' ');
+                    self.expr(value, Precedence::Concatenation);
+                }
+            }
+            Stmt::Put { value, .. } => {
+                self.line("put ");
+                self.expr(value, Precedence::Concatenation);
+            }
+            Stmt::Relax { .. } => self.line("relax"),
+            Stmt::While {
+                condition, body, ..
+            } => {
+                self.line("while ");
+                self.expr(condition, Precedence::Concatenation);
+                self.out.push_str(" do");
+                self.block(body);
+                self.line("end");
+            }
+            Stmt::Error { .. } => self.line("<error>"),
+        }
+    }
+
+    // writes an expression that is an operand of operators binding at least
+    // as tightly as `min`, in parentheses if it binds looser
+    fn expr(&mut self, expr: &Expr, min: Precedence) {
+        if precedence(expr) < min {
+            self.out.push('(');
+            self.expr(expr, Precedence::Concatenation);
+            self.out.push(')');
+            return;
+        }
+
+        match expr {
+            Expr::Variable(ident) => self.ident(*ident),
+            Expr::Number { value, .. } => self.out.push_str(&value.to_string()),
+            Expr::Boolean { value, .. } => self.out.push_str(if *value { "true" } else { "false" }),
+            Expr::String { value, .. } => self.string(self.interner.resolve(*value)),
+            Expr::Unary { op, operand, .. } => match op {
+                // "-" negates a whole term
+                UnaryOp::Negate => {
+                    self.out.push('-');
+                    self.expr(operand, Precedence::Multiplicative);
+                }
+                UnaryOp::Not => {
+                    self.out.push_str("not ");
+                    self.expr(operand, Precedence::Factor);
+                }
+            },
+            Expr::Binary {
+                op, left, right, ..
+            } => {
+                // operators group to the left, and relational operators do
+                // not group at all
+                let precedence = op.precedence();
+                let left_min = if op.is_associative() {
+                    precedence
+                } else {
+                    precedence.tighter()
+                };
+                self.expr(left, left_min);
+                self.out.push(' ');
+                self.out.push_str(op_token(*op).text().unwrap());
+                self.out.push(' ');
+                self.expr(right, precedence.tighter());
+            }
+            Expr::Index { array, index, .. } => {
+                self.ident(*array);
+                self.index(index);
+            }
+            Expr::Call { name, args, .. } => {
+                self.ident(*name);
+                self.args(args);
+            }
+            Expr::Error { .. } => self.out.push_str("<error>"),
+        }
+    }
+
+    // "[" simple "]"
+    fn index(&mut self, index: &Expr) {
+        self.out.push('[');
+        self.expr(index, Precedence::Additive);
+        self.out.push(']');
+    }
+
+    // "(" [expr {"," expr}] ")"
+    fn args(&mut self, args: &[Expr]) {
+        self.out.push('(');
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(arg, Precedence::Concatenation);
+        }
+        self.out.push(')');
+    }
+
+    // a string literal, escaping what cannot be written as it is
+    fn string(&mut self, value: &str) {
+        self.out.push('"');
+        for ch in value.chars() {
+            match ch {
+                '\n' => self.out.push_str("\\n"),
+                '\t' => self.out.push_str("\\t"),
+                '\r' => self.out.push_str("\\r"),
+                '\0' => self.out.push_str("\\0"),
+                '"' => self.out.push_str("\\\""),
+                '\\' => self.out.push_str("\\\\"),
+                ch if ch.is_ascii_control() => self.out.push_str(&format!("\\x{:02X}", ch as u8)),
+                ch => self.out.push(ch),
+            }
+        }
+        self.out.push('"');
+    }
+
+    fn ident(&mut self, ident: Ident) {
+        self.out.push_str(self.interner.resolve(ident.symbol));
+    }
+
+    fn idents(&mut self, idents: &[Ident]) {
+        for (i, ident) in idents.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.ident(*ident);
+        }
+    }
+
+    // starts a new line at the current indentation with `text`
+    fn line(&mut self, text: &str) {
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.out.push_str(&" ".repeat(self.indent * INDENT));
+        self.out.push_str(text);
+    }
+}
+
+// how tightly an expression binds as an operand. A negation is only allowed
+// where an additive expression may start, so it binds like one.
+fn precedence(expr: &Expr) -> Precedence {
+    match expr {
+        Expr::Binary { op, .. } => op.precedence(),
+        Expr::Unary {
+            op: UnaryOp::Negate,
+            ..
+        } => Precedence::Additive,
+        _ => Precedence::Factor,
+    }
+}
+
+// the token of a binary operator
+fn op_token(op: BinaryOp) -> TokenKind {
+    match op {
+        BinaryOp::Concatenate => TokenKind::Concatenate,
+        BinaryOp::Equal => TokenKind::Equal,
+        BinaryOp::GreaterEqual => TokenKind::GreaterEqual,
+        BinaryOp::GreaterThan => TokenKind::GreaterThan,
+        BinaryOp::LessEqual => TokenKind::LessEqual,
+        BinaryOp::LessThan => TokenKind::LessThan,
+        BinaryOp::NotEqual => TokenKind::NotEqual,
+        BinaryOp::Subtract => TokenKind::Minus,
+        BinaryOp::Or => TokenKind::Or,
+        BinaryOp::Add => TokenKind::Plus,
+        BinaryOp::And => TokenKind::And,
+        BinaryOp::Divide => TokenKind::Divide,
+        BinaryOp::Multiply => TokenKind::Multiply,
+        BinaryOp::Remainder => TokenKind::Remainder,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseError;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    // parses `source`, sharing `interner` so that the symbols of two parses
+    // can be compared
+    fn parse(source: &str, interner: Interner) -> Result<(Program, Interner), ParseError> {
+        let mut parser = Parser::new(Lexer::new(source.as_bytes()).with_interner(interner));
+        let program = parser.parse_program()?;
+        Ok((program, parser.into_interner()))
+    }
+
+    // the debug form of an AST with its spans left out, since printing a
+    // program moves its nodes around
+    fn without_spans(program: &Program) -> String {
+        let debug = format!("{:?}", program);
+        let mut out = String::new();
+        let mut rest = debug.as_str();
+        while let Some(start) = rest.find("Span {") {
+            out.push_str(&rest[..start]);
+            rest = &rest[start..];
+            rest = &rest[rest.find('}').unwrap() + 1..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    // checks that parse(print(parse(source))) == parse(source), returning
+    // the printed source
+    fn round_trip(source: &str) -> String {
+        let (program, interner) = parse(source, Interner::new()).unwrap();
+        let printed = print_program(&program, &interner);
+        let (reparsed, interner) = parse(&printed, interner)
+            .unwrap_or_else(|err| panic!("{}\nin the printed source:\n{}", err, printed));
+        assert_eq!(
+            without_spans(&reparsed),
+            without_spans(&program),
+            "printed source:\n{}",
+            printed
+        );
+        assert_eq!(print_program(&reparsed, &interner), printed);
+        printed
+    }
+
+    #[test]
+    fn test_print_program() {
+        let source = "source  demo {! Adds one. }
+function inc(integer n, boolean array flags) to integer begin leave n+1 end
+function show() begin relax end
+begin integer array a; boolean done, ok;
+a:=array 10; get a[0];
+if a[0] > 1 then put \"big\" elsif done then relax else call inc(a[0], 1); done := true end;
+while not done do done := true end; leave end";
+        assert_eq!(
+            round_trip(source),
+            "source demo
+{! Adds one. }
+function inc(integer n, boolean array flags) to integer
+begin
+  leave n + 1
+end
+function show()
+begin
+  relax
+end
+begin
+  integer array a;
+  boolean done, ok;
+  a := array 10;
+  get a[0];
+  if a[0] > 1 then
+    put \"big\"
+  elsif done then
+    relax
+  else
+    call inc(a[0], 1);
+    done := true
+  end;
+  while not done do
+    done := true
+  end;
+  leave
+end
+"
+        );
+    }
+
+    #[test]
+    fn test_print_parentheses() {
+        let cases = [
+            ("a - (b - c)", "a - (b - c)"),
+            ("(a - b) - c", "a - b - c"),
+            ("(a + b) * c", "(a + b) * c"),
+            ("(a = b) = c", "(a = b) = c"),
+            ("a = (b < c)", "a = (b < c)"),
+            ("-a * b", "-a * b"),
+            ("(-a) * b", "(-a) * b"),
+            ("a + (-b)", "a + (-b)"),
+            ("not (a and b)", "not (a and b)"),
+            ("not (-a)", "not (-a)"),
+            ("-(-a)", "-(-a)"),
+            ("-(a + b)", "-(a + b)"),
+            ("x[(a < b) + 1]", "x[(a < b) + 1]"),
+            ("x[-1]", "x[-1]"),
+            ("f((a . b), (c))", "f(a . b, c)"),
+            ("a . (b . c)", "a . (b . c)"),
+            ("-2147483648 + 2", "-2147483648 + 2"),
+        ];
+        for (expr, printed) in cases {
+            let source = format!("source e begin put {} end", expr);
+            let (program, interner) = parse(&source, Interner::new()).unwrap();
+            match &program.body.statements[0] {
+                Stmt::Put { value, .. } => assert_eq!(print_expr(value, &interner), printed),
+                stmt => panic!("expected a put statement, found {:?}", stmt),
+            }
+            round_trip(&source);
+        }
+    }
+
+    #[test]
+    fn test_print_strings() {
+        let source = "source s begin put \"tab\\t\\\"quoted\\\" \\\\ \\x01 \\u{e9}\" end";
+        let printed = round_trip(source);
+        assert!(printed.contains("put \"tab\\t\\\"quoted\\\" \\\\ \\x01 é\""));
+    }
+}
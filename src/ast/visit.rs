@@ -0,0 +1,459 @@
+use super::{
+    AssignValue, Body, Expr, FunctionDecl, Ident, IfBranch, Param, Program, Stmt, Type, VarDecl,
+};
+
+// Passes over the AST implement Visitor (or VisitorMut to change the tree in
+// place) and override the methods for the nodes they care about. Every method
+// defaults to the matching walk function, which visits the children of the
+// node in source order, so an overriding method calls it to carry on into the
+// children, or leaves it out to skip them.
+
+pub trait Visitor {
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program);
+    }
+
+    fn visit_function(&mut self, function: &FunctionDecl) {
+        walk_function(self, function);
+    }
+
+    fn visit_param(&mut self, param: &Param) {
+        walk_param(self, param);
+    }
+
+    fn visit_body(&mut self, body: &Body) {
+        walk_body(self, body);
+    }
+
+    fn visit_var_decl(&mut self, var: &VarDecl) {
+        walk_var_decl(self, var);
+    }
+
+    fn visit_type(&mut self, _ty: &Type) {}
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt);
+    }
+
+    // the "if" or one of the "elsif" arms of an if statement
+    fn visit_if_branch(&mut self, branch: &IfBranch) {
+        walk_if_branch(self, branch);
+    }
+
+    fn visit_assign_value(&mut self, value: &AssignValue) {
+        walk_assign_value(self, value);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+
+    // every name, whether it declares or uses a variable or function
+    fn visit_ident(&mut self, _ident: &Ident) {}
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &Program) {
+    visitor.visit_ident(&program.name);
+    for function in &program.functions {
+        visitor.visit_function(function);
+    }
+    visitor.visit_body(&program.body);
+}
+
+pub fn walk_function<V: Visitor + ?Sized>(visitor: &mut V, function: &FunctionDecl) {
+    visitor.visit_ident(&function.name);
+    for param in &function.params {
+        visitor.visit_param(param);
+    }
+    if let Some(return_type) = &function.return_type {
+        visitor.visit_type(return_type);
+    }
+    visitor.visit_body(&function.body);
+}
+
+pub fn walk_param<V: Visitor + ?Sized>(visitor: &mut V, param: &Param) {
+    visitor.visit_type(&param.ty);
+    visitor.visit_ident(&param.name);
+}
+
+pub fn walk_body<V: Visitor + ?Sized>(visitor: &mut V, body: &Body) {
+    for var in &body.vars {
+        visitor.visit_var_decl(var);
+    }
+    walk_stmts(visitor, &body.statements);
+}
+
+pub fn walk_var_decl<V: Visitor + ?Sized>(visitor: &mut V, var: &VarDecl) {
+    visitor.visit_type(&var.ty);
+    for name in &var.names {
+        visitor.visit_ident(name);
+    }
+}
+
+pub fn walk_stmts<V: Visitor + ?Sized>(visitor: &mut V, statements: &[Stmt]) {
+    for stmt in statements {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, stmt: &Stmt) {
+    match stmt {
+        Stmt::Assign {
+            target,
+            index,
+            value,
+            ..
+        } => {
+            visitor.visit_ident(target);
+            if let Some(index) = index {
+                visitor.visit_expr(index);
+            }
+            visitor.visit_assign_value(value);
+        }
+        Stmt::Call { name, args, .. } => {
+            visitor.visit_ident(name);
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        Stmt::If {
+            branches,
+            else_branch,
+            ..
+        } => {
+            for branch in branches {
+                visitor.visit_if_branch(branch);
+            }
+            if let Some(else_branch) = else_branch {
+                walk_stmts(visitor, else_branch);
+            }
+        }
+        Stmt::Get { target, index, .. } => {
+            visitor.visit_ident(target);
+            if let Some(index) = index {
+                visitor.visit_expr(index);
+            }
+        }
+        Stmt::Leave { value, .. } => {
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+        Stmt::Put { value, .. } => visitor.visit_expr(value),
+        Stmt::While {
+            condition, body, ..
+        } => {
+            visitor.visit_expr(condition);
+            walk_stmts(visitor, body);
+        }
+        Stmt::Relax { .. } | Stmt::Error { .. } => {}
+    }
+}
+
+pub fn walk_if_branch<V: Visitor + ?Sized>(visitor: &mut V, branch: &IfBranch) {
+    visitor.visit_expr(&branch.condition);
+    walk_stmts(visitor, &branch.body);
+}
+
+pub fn walk_assign_value<V: Visitor + ?Sized>(visitor: &mut V, value: &AssignValue) {
+    match value {
+        AssignValue::Expr(expr) => visitor.visit_expr(expr),
+        AssignValue::Array { length, .. } => visitor.visit_expr(length),
+    }
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Variable(ident) => visitor.visit_ident(ident),
+        Expr::Unary { operand, .. } => visitor.visit_expr(operand),
+        Expr::Binary { left, right, .. } => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        }
+        Expr::Index { array, index, .. } => {
+            visitor.visit_ident(array);
+            visitor.visit_expr(index);
+        }
+        Expr::Call { name, args, .. } => {
+            visitor.visit_ident(name);
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        Expr::Number { .. } | Expr::Boolean { .. } | Expr::String { .. } | Expr::Error { .. } => {}
+    }
+}
+
+// the same as Visitor, but the nodes can be changed (or replaced) in place
+pub trait VisitorMut {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program);
+    }
+
+    fn visit_function_mut(&mut self, function: &mut FunctionDecl) {
+        walk_function_mut(self, function);
+    }
+
+    fn visit_param_mut(&mut self, param: &mut Param) {
+        walk_param_mut(self, param);
+    }
+
+    fn visit_body_mut(&mut self, body: &mut Body) {
+        walk_body_mut(self, body);
+    }
+
+    fn visit_var_decl_mut(&mut self, var: &mut VarDecl) {
+        walk_var_decl_mut(self, var);
+    }
+
+    fn visit_type_mut(&mut self, _ty: &mut Type) {}
+
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        walk_stmt_mut(self, stmt);
+    }
+
+    fn visit_if_branch_mut(&mut self, branch: &mut IfBranch) {
+        walk_if_branch_mut(self, branch);
+    }
+
+    fn visit_assign_value_mut(&mut self, value: &mut AssignValue) {
+        walk_assign_value_mut(self, value);
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+    }
+
+    fn visit_ident_mut(&mut self, _ident: &mut Ident) {}
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(visitor: &mut V, program: &mut Program) {
+    visitor.visit_ident_mut(&mut program.name);
+    for function in &mut program.functions {
+        visitor.visit_function_mut(function);
+    }
+    visitor.visit_body_mut(&mut program.body);
+}
+
+pub fn walk_function_mut<V: VisitorMut + ?Sized>(visitor: &mut V, function: &mut FunctionDecl) {
+    visitor.visit_ident_mut(&mut function.name);
+    for param in &mut function.params {
+        visitor.visit_param_mut(param);
+    }
+    if let Some(return_type) = &mut function.return_type {
+        visitor.visit_type_mut(return_type);
+    }
+    visitor.visit_body_mut(&mut function.body);
+}
+
+pub fn walk_param_mut<V: VisitorMut + ?Sized>(visitor: &mut V, param: &mut Param) {
+    visitor.visit_type_mut(&mut param.ty);
+    visitor.visit_ident_mut(&mut param.name);
+}
+
+pub fn walk_body_mut<V: VisitorMut + ?Sized>(visitor: &mut V, body: &mut Body) {
+    for var in &mut body.vars {
+        visitor.visit_var_decl_mut(var);
+    }
+    walk_stmts_mut(visitor, &mut body.statements);
+}
+
+pub fn walk_var_decl_mut<V: VisitorMut + ?Sized>(visitor: &mut V, var: &mut VarDecl) {
+    visitor.visit_type_mut(&mut var.ty);
+    for name in &mut var.names {
+        visitor.visit_ident_mut(name);
+    }
+}
+
+pub fn walk_stmts_mut<V: VisitorMut + ?Sized>(visitor: &mut V, statements: &mut [Stmt]) {
+    for stmt in statements {
+        visitor.visit_stmt_mut(stmt);
+    }
+}
+
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Stmt) {
+    match stmt {
+        Stmt::Assign {
+            target,
+            index,
+            value,
+            ..
+        } => {
+            visitor.visit_ident_mut(target);
+            if let Some(index) = index {
+                visitor.visit_expr_mut(index);
+            }
+            visitor.visit_assign_value_mut(value);
+        }
+        Stmt::Call { name, args, .. } => {
+            visitor.visit_ident_mut(name);
+            for arg in args {
+                visitor.visit_expr_mut(arg);
+            }
+        }
+        Stmt::If {
+            branches,
+            else_branch,
+            ..
+        } => {
+            for branch in branches {
+                visitor.visit_if_branch_mut(branch);
+            }
+            if let Some(else_branch) = else_branch {
+                walk_stmts_mut(visitor, else_branch);
+            }
+        }
+        Stmt::Get { target, index, .. } => {
+            visitor.visit_ident_mut(target);
+            if let Some(index) = index {
+                visitor.visit_expr_mut(index);
+            }
+        }
+        Stmt::Leave { value, .. } => {
+            if let Some(value) = value {
+                visitor.visit_expr_mut(value);
+            }
+        }
+        Stmt::Put { value, .. } => visitor.visit_expr_mut(value),
+        Stmt::While {
+            condition, body, ..
+        } => {
+            visitor.visit_expr_mut(condition);
+            walk_stmts_mut(visitor, body);
+        }
+        Stmt::Relax { .. } | Stmt::Error { .. } => {}
+    }
+}
+
+pub fn walk_if_branch_mut<V: VisitorMut + ?Sized>(visitor: &mut V, branch: &mut IfBranch) {
+    visitor.visit_expr_mut(&mut branch.condition);
+    walk_stmts_mut(visitor, &mut branch.body);
+}
+
+pub fn walk_assign_value_mut<V: VisitorMut + ?Sized>(visitor: &mut V, value: &mut AssignValue) {
+    match value {
+        AssignValue::Expr(expr) => visitor.visit_expr_mut(expr),
+        AssignValue::Array { length, .. } => visitor.visit_expr_mut(length),
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Variable(ident) => visitor.visit_ident_mut(ident),
+        Expr::Unary { operand, .. } => visitor.visit_expr_mut(operand),
+        Expr::Binary { left, right, .. } => {
+            visitor.visit_expr_mut(left);
+            visitor.visit_expr_mut(right);
+        }
+        Expr::Index { array, index, .. } => {
+            visitor.visit_ident_mut(array);
+            visitor.visit_expr_mut(index);
+        }
+        Expr::Call { name, args, .. } => {
+            visitor.visit_ident_mut(name);
+            for arg in args {
+                visitor.visit_expr_mut(arg);
+            }
+        }
+        Expr::Number { .. } | Expr::Boolean { .. } | Expr::String { .. } | Expr::Error { .. } => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::printer::print_program;
+    use crate::ast::BinaryOp;
+    use crate::intern::Interner;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn parse(source: &str) -> (Program, Interner) {
+        let mut parser = Parser::new(Lexer::new(source.as_bytes()));
+        let program = parser.parse_program().unwrap();
+        (program, parser.into_interner())
+    }
+
+    const SOURCE: &str = "source demo
+function f(integer n) to integer begin leave n * 2 end
+begin
+  integer array a; integer i;
+  a := array 1 + 2;
+  if i > 0 then get a[i] elsif i = 0 then relax else call f(a[3 + 4]) end;
+  while i < 10 do i := i + f(i) end;
+  put \"i: \" . i
+end";
+
+    #[test]
+    fn test_visitor_reaches_every_node() {
+        // collects the names in the order they are visited, and counts the
+        // statements
+        struct Names<'i> {
+            interner: &'i Interner,
+            names: Vec<&'i str>,
+            statements: usize,
+        }
+
+        impl Visitor for Names<'_> {
+            fn visit_stmt(&mut self, stmt: &Stmt) {
+                self.statements += 1;
+                walk_stmt(self, stmt);
+            }
+
+            fn visit_ident(&mut self, ident: &Ident) {
+                self.names.push(self.interner.resolve(ident.symbol));
+            }
+        }
+
+        let (program, interner) = parse(SOURCE);
+        let mut visitor = Names {
+            interner: &interner,
+            names: Vec::new(),
+            statements: 0,
+        };
+        visitor.visit_program(&program);
+        assert_eq!(
+            visitor.names,
+            [
+                "demo", "f", "n", "n", "a", "i", "a", "i", "a", "i", "i", "f", "a", "i", "i", "i",
+                "f", "i", "i"
+            ]
+        );
+        assert_eq!(visitor.statements, 9);
+    }
+
+    #[test]
+    fn test_visitor_mut_rewrites_in_place() {
+        // folds additions of two number literals, children first
+        struct FoldAdditions;
+
+        impl VisitorMut for FoldAdditions {
+            fn visit_expr_mut(&mut self, expr: &mut Expr) {
+                walk_expr_mut(self, expr);
+                if let Expr::Binary {
+                    op: BinaryOp::Add,
+                    left,
+                    right,
+                    span,
+                } = expr
+                {
+                    if let (Expr::Number { value: left, .. }, Expr::Number { value: right, .. }) =
+                        (&**left, &**right)
+                    {
+                        *expr = Expr::Number {
+                            value: left + right,
+                            span: *span,
+                        };
+                    }
+                }
+            }
+        }
+
+        let (mut program, interner) = parse(SOURCE);
+        FoldAdditions.visit_program_mut(&mut program);
+        let printed = print_program(&program, &interner);
+        assert!(printed.contains("a := array 3;"));
+        assert!(printed.contains("call f(a[7])"));
+        assert!(printed.contains("i := i + f(i)"));
+    }
+}
@@ -0,0 +1,414 @@
+pub mod view;
+
+use std::fmt;
+use std::rc::Rc;
+
+use crate::span::Span;
+use crate::token::{TokenKind, TriviaKind};
+
+// The concrete syntax tree keeps every byte of the source, including
+// whitespace and comments, so that tools such as a formatter or an editor can
+// work on the exact text that was parsed. It is built in two layers:
+//
+// - green nodes, which are immutable, know only their kind, their children
+//   and the length of their text, so that they could be shared between trees
+// - red nodes (SyntaxNode and SyntaxToken), which are made on demand while
+//   walking the tree and add a parent and an offset into the source
+//
+// The parser builds it alongside the AST (see Parser::with_syntax_tree). The
+// views in view.rs give its nodes the structure of the AST, so that tools can
+// work on the CST alone rather than matching AST nodes to it by span.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+// the kinds of inner nodes of the syntax tree
+pub enum NodeKind {
+    Program,
+    Function,
+    // "(" [param {"," param}] ")" of a function
+    ParamList,
+    Param,
+    Type,
+    Body,
+    VarDecl,
+
+    // statements
+    AssignStmt,
+    CallStmt,
+    IfStmt,
+    GetStmt,
+    LeaveStmt,
+    PutStmt,
+    RelaxStmt,
+    WhileStmt,
+
+    // "array" simple, on the right of an assignment
+    ArrayAlloc,
+    // "[" simple "]" after the target of an assignment or "get"
+    Index,
+    // "(" [expr {"," expr}] ")" of a call
+    ArgList,
+
+    // expressions
+    NameRef,
+    Literal,
+    ParenExpr,
+    UnaryExpr,
+    BinaryExpr,
+    IndexExpr,
+    CallExpr,
+
+    // tokens skipped while recovering from a syntax error, together with what
+    // was parsed of the construct they belong to
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+// the kinds of leaves of the syntax tree
+pub enum LeafKind {
+    Token(TokenKind),
+    Trivia(TriviaKind),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+// an immutable leaf with its text
+pub struct GreenToken {
+    kind: LeafKind,
+    text: String,
+}
+
+impl GreenToken {
+    pub fn new(kind: LeafKind, text: String) -> Self {
+        GreenToken { kind, text }
+    }
+
+    pub fn kind(&self) -> LeafKind {
+        self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+// an immutable inner node, which does not know where it is in the source
+pub struct GreenNode {
+    kind: NodeKind,
+    children: Vec<GreenElement>,
+
+    // the length in bytes of the text of the node
+    len: usize,
+}
+
+impl GreenNode {
+    pub fn new(kind: NodeKind, children: Vec<GreenElement>) -> Self {
+        let len = children.iter().map(GreenElement::len).sum();
+        GreenNode {
+            kind,
+            children,
+            len,
+        }
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.kind
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GreenElement {
+    Node(Rc<GreenNode>),
+    Token(Rc<GreenToken>),
+}
+
+impl GreenElement {
+    pub fn len(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.len(),
+            GreenElement::Token(token) => token.text.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+// a place in the children of the node being built, see GreenBuilder::marker
+pub(crate) struct Marker {
+    // the number of children of the node being built
+    children: usize,
+
+    // the number of nodes being built
+    depth: usize,
+}
+
+#[derive(Debug, Default)]
+// builds a green tree from the tokens and nodes the parser goes through
+pub(crate) struct GreenBuilder {
+    // the kind of every node being built, and where its children start in
+    // `children`
+    parents: Vec<(NodeKind, usize)>,
+
+    // the children of the nodes being built, innermost last
+    children: Vec<GreenElement>,
+
+    // trivia (and doc comments) seen but not added yet. It is added right
+    // before the next token or node, so that nodes do not start or end with
+    // trivia.
+    pending: Vec<GreenElement>,
+}
+
+impl GreenBuilder {
+    pub(crate) fn new() -> Self {
+        GreenBuilder::default()
+    }
+
+    // holds on to a leaf that does not belong to the grammar until the next
+    // token or node is added
+    pub(crate) fn trivia(&mut self, kind: LeafKind, text: String) {
+        self.pending
+            .push(GreenElement::Token(Rc::new(GreenToken::new(kind, text))));
+    }
+
+    pub(crate) fn token(&mut self, kind: TokenKind, text: String) {
+        self.flush();
+        self.children
+            .push(GreenElement::Token(Rc::new(GreenToken::new(
+                LeafKind::Token(kind),
+                text,
+            ))));
+    }
+
+    pub(crate) fn start_node(&mut self, kind: NodeKind) {
+        self.flush();
+        self.parents.push((kind, self.children.len()));
+    }
+
+    // starts a node whose children begin at `marker`, for when it is only
+    // known after parsing its first child what kind of node it is
+    pub(crate) fn start_node_at(&mut self, marker: Marker, kind: NodeKind) {
+        assert!(marker.depth == self.parents.len());
+        self.parents.push((kind, marker.children));
+    }
+
+    pub(crate) fn finish_node(&mut self) {
+        let (kind, start) = self.parents.pop().unwrap();
+        let children = self.children.split_off(start);
+        self.children
+            .push(GreenElement::Node(Rc::new(GreenNode::new(kind, children))));
+    }
+
+    // the current place in the node being built
+    pub(crate) fn marker(&mut self) -> Marker {
+        self.flush();
+        Marker {
+            children: self.children.len(),
+            depth: self.parents.len(),
+        }
+    }
+
+    // finishes the nodes started after `marker`, which an error left open
+    pub(crate) fn finish_nodes_to(&mut self, marker: Marker) {
+        while self.parents.len() > marker.depth {
+            self.finish_node();
+        }
+    }
+
+    // the root of the tree, once every node has been finished
+    pub(crate) fn finish(mut self) -> Rc<GreenNode> {
+        self.flush();
+        assert!(self.parents.is_empty() && self.children.len() == 1);
+        match self.children.pop() {
+            Some(GreenElement::Node(root)) => root,
+            _ => panic!("the syntax tree has no root node"),
+        }
+    }
+
+    fn flush(&mut self) {
+        self.children.append(&mut self.pending);
+    }
+}
+
+#[derive(Clone)]
+// an inner node of the syntax tree together with where it is in the source
+pub struct SyntaxNode(Rc<NodeData>);
+
+struct NodeData {
+    green: Rc<GreenNode>,
+    parent: Option<SyntaxNode>,
+
+    // the byte offset of the start of the node
+    offset: usize,
+}
+
+#[derive(Clone)]
+// a leaf of the syntax tree together with where it is in the source
+pub struct SyntaxToken {
+    green: Rc<GreenToken>,
+    parent: SyntaxNode,
+    offset: usize,
+}
+
+#[derive(Clone, Debug)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: Rc<GreenNode>) -> Self {
+        SyntaxNode(Rc::new(NodeData {
+            green,
+            parent: None,
+            offset: 0,
+        }))
+    }
+
+    pub fn green(&self) -> &Rc<GreenNode> {
+        &self.0.green
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.0.green.kind
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.0.offset, self.0.offset + self.0.green.len)
+    }
+
+    pub fn parent(&self) -> Option<SyntaxNode> {
+        self.0.parent.clone()
+    }
+
+    // the source text of the node, trivia included
+    pub fn text(&self) -> String {
+        self.tokens().iter().map(SyntaxToken::text).collect()
+    }
+
+    // the child nodes and tokens of the node, in source order
+    pub fn children_with_tokens(&self) -> Vec<SyntaxElement> {
+        let mut offset = self.0.offset;
+        let mut elements = Vec::new();
+        for child in &self.0.green.children {
+            elements.push(match child {
+                GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                    green: Rc::clone(green),
+                    parent: Some(self.clone()),
+                    offset,
+                }))),
+                GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                    green: Rc::clone(green),
+                    parent: self.clone(),
+                    offset,
+                }),
+            });
+            offset += child.len();
+        }
+        elements
+    }
+
+    // the child nodes of the node, in source order
+    pub fn children(&self) -> Vec<SyntaxNode> {
+        self.children_with_tokens()
+            .into_iter()
+            .filter_map(|element| match element {
+                SyntaxElement::Node(node) => Some(node),
+                SyntaxElement::Token(_) => None,
+            })
+            .collect()
+    }
+
+    // the node and all nodes below it, in source order
+    pub fn descendants(&self) -> Vec<SyntaxNode> {
+        let mut nodes = vec![self.clone()];
+        for child in self.children() {
+            nodes.extend(child.descendants());
+        }
+        nodes
+    }
+
+    // every leaf below the node, trivia included, in source order
+    pub fn tokens(&self) -> Vec<SyntaxToken> {
+        let mut tokens = Vec::new();
+        for element in self.children_with_tokens() {
+            match element {
+                SyntaxElement::Node(node) => tokens.extend(node.tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> LeafKind {
+        self.green.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.offset, self.offset + self.green.text.len())
+    }
+
+    pub fn parent(&self) -> SyntaxNode {
+        self.parent.clone()
+    }
+
+    // the kind of the token, or None for trivia
+    pub fn token_kind(&self) -> Option<TokenKind> {
+        match self.green.kind {
+            LeafKind::Token(kind) if !self.is_trivia() => Some(kind),
+            _ => None,
+        }
+    }
+
+    pub fn is_trivia(&self) -> bool {
+        !matches!(self.green.kind, LeafKind::Token(kind) if kind != TokenKind::DocComment)
+    }
+}
+
+// writes the source text of the node
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text())
+    }
+}
+
+// writes the kind and span of the node, such as "Body@12..40"
+impl fmt::Debug for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let span = self.span();
+        write!(f, "{:?}@{}..{}", self.kind(), span.start, span.end)
+    }
+}
+
+impl fmt::Debug for SyntaxToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let span = self.span();
+        write!(
+            f,
+            "{:?}@{}..{} {:?}",
+            self.kind(),
+            span.start,
+            span.end,
+            self.text()
+        )
+    }
+}
@@ -0,0 +1,536 @@
+use crate::ast::{BaseType, BinaryOp, UnaryOp};
+use crate::parser::binary_op;
+use crate::token::TokenKind;
+
+use super::{NodeKind, SyntaxElement, SyntaxNode, SyntaxToken};
+
+// Typed views of the syntax tree, with the structure of the AST. Each wraps a
+// SyntaxNode of one kind and finds its parts among the children of that node,
+// so that a formatter or an editor can walk the tree by what its nodes mean
+// and get the exact node (trivia and all) of any part back with `syntax`.
+//
+// A part is None (or left out of a list) when a syntax error kept it from
+// being parsed, as a tree built in recovery mode has Error nodes in its place.
+
+// a typed view of a syntax node
+pub trait View: Sized {
+    // the view of `node`, or None if it is not of the kind viewed
+    fn cast(node: SyntaxNode) -> Option<Self>;
+
+    fn syntax(&self) -> &SyntaxNode;
+}
+
+// defines a view of the nodes of a single kind
+macro_rules! define_views {
+    ($($name:ident => $kind:ident,)*) => {
+        $(
+            #[derive(Debug, Clone)]
+            pub struct $name(SyntaxNode);
+
+            impl View for $name {
+                fn cast(node: SyntaxNode) -> Option<Self> {
+                    (node.kind() == NodeKind::$kind).then_some($name(node))
+                }
+
+                fn syntax(&self) -> &SyntaxNode {
+                    &self.0
+                }
+            }
+        )*
+    };
+}
+
+define_views! {
+    Program => Program,
+    Function => Function,
+    ParamList => ParamList,
+    Param => Param,
+    Type => Type,
+    Body => Body,
+    VarDecl => VarDecl,
+    AssignStmt => AssignStmt,
+    CallStmt => CallStmt,
+    IfStmt => IfStmt,
+    GetStmt => GetStmt,
+    LeaveStmt => LeaveStmt,
+    PutStmt => PutStmt,
+    RelaxStmt => RelaxStmt,
+    WhileStmt => WhileStmt,
+    ArrayAlloc => ArrayAlloc,
+    Index => Index,
+    ArgList => ArgList,
+    NameRef => NameRef,
+    Literal => Literal,
+    ParenExpr => ParenExpr,
+    UnaryExpr => UnaryExpr,
+    BinaryExpr => BinaryExpr,
+    IndexExpr => IndexExpr,
+    CallExpr => CallExpr,
+    ErrorNode => Error,
+}
+
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    Assign(AssignStmt),
+    Call(CallStmt),
+    If(IfStmt),
+    Get(GetStmt),
+    Leave(LeaveStmt),
+    Put(PutStmt),
+    Relax(RelaxStmt),
+    While(WhileStmt),
+    Error(ErrorNode),
+}
+
+impl View for Stmt {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        let stmt = match node.kind() {
+            NodeKind::AssignStmt => Stmt::Assign(AssignStmt(node)),
+            NodeKind::CallStmt => Stmt::Call(CallStmt(node)),
+            NodeKind::IfStmt => Stmt::If(IfStmt(node)),
+            NodeKind::GetStmt => Stmt::Get(GetStmt(node)),
+            NodeKind::LeaveStmt => Stmt::Leave(LeaveStmt(node)),
+            NodeKind::PutStmt => Stmt::Put(PutStmt(node)),
+            NodeKind::RelaxStmt => Stmt::Relax(RelaxStmt(node)),
+            NodeKind::WhileStmt => Stmt::While(WhileStmt(node)),
+            NodeKind::Error => Stmt::Error(ErrorNode(node)),
+            _ => return None,
+        };
+        Some(stmt)
+    }
+
+    fn syntax(&self) -> &SyntaxNode {
+        match self {
+            Stmt::Assign(stmt) => stmt.syntax(),
+            Stmt::Call(stmt) => stmt.syntax(),
+            Stmt::If(stmt) => stmt.syntax(),
+            Stmt::Get(stmt) => stmt.syntax(),
+            Stmt::Leave(stmt) => stmt.syntax(),
+            Stmt::Put(stmt) => stmt.syntax(),
+            Stmt::Relax(stmt) => stmt.syntax(),
+            Stmt::While(stmt) => stmt.syntax(),
+            Stmt::Error(stmt) => stmt.syntax(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    NameRef(NameRef),
+    Literal(Literal),
+    Paren(ParenExpr),
+    Unary(UnaryExpr),
+    Binary(BinaryExpr),
+    Index(IndexExpr),
+    Call(CallExpr),
+    Error(ErrorNode),
+}
+
+impl View for Expr {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        let expr = match node.kind() {
+            NodeKind::NameRef => Expr::NameRef(NameRef(node)),
+            NodeKind::Literal => Expr::Literal(Literal(node)),
+            NodeKind::ParenExpr => Expr::Paren(ParenExpr(node)),
+            NodeKind::UnaryExpr => Expr::Unary(UnaryExpr(node)),
+            NodeKind::BinaryExpr => Expr::Binary(BinaryExpr(node)),
+            NodeKind::IndexExpr => Expr::Index(IndexExpr(node)),
+            NodeKind::CallExpr => Expr::Call(CallExpr(node)),
+            NodeKind::Error => Expr::Error(ErrorNode(node)),
+            _ => return None,
+        };
+        Some(expr)
+    }
+
+    fn syntax(&self) -> &SyntaxNode {
+        match self {
+            Expr::NameRef(expr) => expr.syntax(),
+            Expr::Literal(expr) => expr.syntax(),
+            Expr::Paren(expr) => expr.syntax(),
+            Expr::Unary(expr) => expr.syntax(),
+            Expr::Binary(expr) => expr.syntax(),
+            Expr::Index(expr) => expr.syntax(),
+            Expr::Call(expr) => expr.syntax(),
+            Expr::Error(expr) => expr.syntax(),
+        }
+    }
+}
+
+// the first child of `node` with a view of type T
+fn child<T: View>(node: &SyntaxNode) -> Option<T> {
+    node.children().into_iter().find_map(T::cast)
+}
+
+// the children of `node` with a view of type T, in source order
+fn children<T: View>(node: &SyntaxNode) -> Vec<T> {
+    node.children().into_iter().filter_map(T::cast).collect()
+}
+
+// the tokens of `node` itself, trivia left out
+fn tokens(node: &SyntaxNode) -> Vec<SyntaxToken> {
+    node.children_with_tokens()
+        .into_iter()
+        .filter_map(|element| match element {
+            SyntaxElement::Token(token) if !token.is_trivia() => Some(token),
+            _ => None,
+        })
+        .collect()
+}
+
+// the first token of `node` itself of the given kind
+fn token(node: &SyntaxNode, kind: TokenKind) -> Option<SyntaxToken> {
+    tokens(node)
+        .into_iter()
+        .find(|token| token.token_kind() == Some(kind))
+}
+
+// the statements among `elements`, which are the children of a node that
+// holds a list of statements along with its keywords
+fn statements(elements: &[SyntaxElement]) -> Vec<Stmt> {
+    elements
+        .iter()
+        .filter_map(|element| match element {
+            SyntaxElement::Node(node) => Stmt::cast(node.clone()),
+            SyntaxElement::Token(_) => None,
+        })
+        .collect()
+}
+
+// the tokens below `node`, trivia left out
+fn tokens_below(node: &SyntaxNode) -> Vec<SyntaxToken> {
+    node.tokens()
+        .into_iter()
+        .filter(|token| !token.is_trivia())
+        .collect()
+}
+
+// the expressions among `elements`
+fn exprs(elements: &[SyntaxElement]) -> impl Iterator<Item = Expr> + '_ {
+    elements.iter().filter_map(|element| match element {
+        SyntaxElement::Node(node) => Expr::cast(node.clone()),
+        SyntaxElement::Token(_) => None,
+    })
+}
+
+// whether `element` is the (non-trivia) token of the given kind
+fn is_token(element: &SyntaxElement, kind: TokenKind) -> bool {
+    matches!(element, SyntaxElement::Token(token) if token.token_kind() == Some(kind))
+}
+
+impl Program {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenKind::Id)
+    }
+
+    // the functions whose header could be parsed
+    pub fn functions(&self) -> Vec<Function> {
+        children(&self.0)
+    }
+
+    pub fn body(&self) -> Option<Body> {
+        child(&self.0)
+    }
+}
+
+impl Function {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenKind::Id)
+    }
+
+    pub fn param_list(&self) -> Option<ParamList> {
+        child(&self.0)
+    }
+
+    // the type after "to", None for a procedure
+    pub fn return_type(&self) -> Option<Type> {
+        child(&self.0)
+    }
+
+    pub fn body(&self) -> Option<Body> {
+        child(&self.0)
+    }
+}
+
+impl ParamList {
+    pub fn params(&self) -> Vec<Param> {
+        children(&self.0)
+    }
+}
+
+impl Param {
+    pub fn ty(&self) -> Option<Type> {
+        child(&self.0)
+    }
+
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenKind::Id)
+    }
+}
+
+impl Type {
+    pub fn base(&self) -> Option<BaseType> {
+        tokens(&self.0)
+            .iter()
+            .find_map(|token| match token.token_kind() {
+                Some(TokenKind::Boolean) => Some(BaseType::Boolean),
+                Some(TokenKind::Integer) => Some(BaseType::Integer),
+                _ => None,
+            })
+    }
+
+    pub fn is_array(&self) -> bool {
+        token(&self.0, TokenKind::Array).is_some()
+    }
+}
+
+impl Body {
+    pub fn vars(&self) -> Vec<VarDecl> {
+        children(&self.0)
+    }
+
+    // the statements after the variable declarations. A declaration with a
+    // syntax error is an Error node too, but one that starts with a type, which
+    // a statement never does.
+    pub fn statements(&self) -> Vec<Stmt> {
+        let nodes = self.0.children();
+        let first = nodes
+            .iter()
+            .position(|node| match node.kind() {
+                NodeKind::VarDecl => false,
+                NodeKind::Error => !tokens_below(node).first().is_some_and(|token| {
+                    matches!(
+                        token.token_kind(),
+                        Some(TokenKind::Boolean | TokenKind::Integer)
+                    )
+                }),
+                _ => true,
+            })
+            .unwrap_or(nodes.len());
+        nodes[first..]
+            .iter()
+            .cloned()
+            .filter_map(Stmt::cast)
+            .collect()
+    }
+}
+
+impl VarDecl {
+    pub fn ty(&self) -> Option<Type> {
+        child(&self.0)
+    }
+
+    pub fn names(&self) -> Vec<SyntaxToken> {
+        tokens(&self.0)
+            .into_iter()
+            .filter(|token| token.token_kind() == Some(TokenKind::Id))
+            .collect()
+    }
+}
+
+impl AssignStmt {
+    pub fn target(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenKind::Id)
+    }
+
+    pub fn index(&self) -> Option<Index> {
+        child(&self.0)
+    }
+
+    // the expression assigned, None for a new array
+    pub fn value(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    // the new array assigned, if any
+    pub fn array_alloc(&self) -> Option<ArrayAlloc> {
+        child(&self.0)
+    }
+}
+
+impl CallStmt {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenKind::Id)
+    }
+
+    pub fn arg_list(&self) -> Option<ArgList> {
+        child(&self.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+// the condition of an "if" or "elsif" and the statements it guards, which
+// unlike in the AST are not a node of their own
+pub struct IfBranch {
+    pub condition: Option<Expr>,
+    pub statements: Vec<Stmt>,
+}
+
+impl IfStmt {
+    pub fn branches(&self) -> Vec<IfBranch> {
+        let elements = self.0.children_with_tokens();
+        let mut branches = Vec::new();
+        let mut rest = elements.as_slice();
+        while let Some(start) = rest.iter().position(|element| {
+            is_token(element, TokenKind::If) || is_token(element, TokenKind::Elsif)
+        }) {
+            rest = &rest[start + 1..];
+            let end = rest
+                .iter()
+                .position(|element| {
+                    [TokenKind::Elsif, TokenKind::Else, TokenKind::End]
+                        .iter()
+                        .any(|kind| is_token(element, *kind))
+                })
+                .unwrap_or(rest.len());
+            let (branch, after) = rest.split_at(end);
+            let then = branch
+                .iter()
+                .position(|element| is_token(element, TokenKind::Then))
+                .unwrap_or(branch.len());
+
+            branches.push(IfBranch {
+                condition: exprs(&branch[..then]).next(),
+                statements: statements(branch.get(then + 1..).unwrap_or_default()),
+            });
+            rest = after;
+        }
+        branches
+    }
+
+    // the statements after "else", if there is one
+    pub fn else_statements(&self) -> Option<Vec<Stmt>> {
+        let elements = self.0.children_with_tokens();
+        let start = elements
+            .iter()
+            .position(|element| is_token(element, TokenKind::Else))?;
+        Some(statements(&elements[start + 1..]))
+    }
+}
+
+impl GetStmt {
+    pub fn target(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenKind::Id)
+    }
+
+    pub fn index(&self) -> Option<Index> {
+        child(&self.0)
+    }
+}
+
+impl LeaveStmt {
+    pub fn value(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl PutStmt {
+    pub fn value(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl WhileStmt {
+    pub fn condition(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn statements(&self) -> Vec<Stmt> {
+        let elements = self.0.children_with_tokens();
+        match elements
+            .iter()
+            .position(|element| is_token(element, TokenKind::Do))
+        {
+            Some(start) => statements(&elements[start + 1..]),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl ArrayAlloc {
+    pub fn length(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl Index {
+    pub fn expr(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl ArgList {
+    pub fn args(&self) -> Vec<Expr> {
+        children(&self.0)
+    }
+}
+
+impl NameRef {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenKind::Id)
+    }
+}
+
+impl Literal {
+    // the number, string, "true" or "false" token
+    pub fn token(&self) -> Option<SyntaxToken> {
+        tokens(&self.0).into_iter().next()
+    }
+}
+
+impl ParenExpr {
+    pub fn expr(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl UnaryExpr {
+    pub fn op(&self) -> Option<UnaryOp> {
+        tokens(&self.0)
+            .iter()
+            .find_map(|token| match token.token_kind() {
+                Some(TokenKind::Minus) => Some(UnaryOp::Negate),
+                Some(TokenKind::Not) => Some(UnaryOp::Not),
+                _ => None,
+            })
+    }
+
+    pub fn operand(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl BinaryExpr {
+    pub fn op(&self) -> Option<BinaryOp> {
+        tokens(&self.0)
+            .iter()
+            .find_map(|token| binary_op(token.token_kind()?))
+    }
+
+    pub fn left(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn right(&self) -> Option<Expr> {
+        children(&self.0).into_iter().nth(1)
+    }
+}
+
+impl IndexExpr {
+    pub fn array(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenKind::Id)
+    }
+
+    pub fn index(&self) -> Option<Index> {
+        child(&self.0)
+    }
+}
+
+impl CallExpr {
+    pub fn name(&self) -> Option<SyntaxToken> {
+        token(&self.0, TokenKind::Id)
+    }
+
+    pub fn arg_list(&self) -> Option<ArgList> {
+        child(&self.0)
+    }
+}
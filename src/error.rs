@@ -1,4 +1,10 @@
-#[derive(Debug, Clone, Copy)]
+use std::{error, fmt};
+
+use crate::span::Span;
+use crate::token::TokenKind;
+use crate::typed::Ty;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 // a place (or position) in the source file
 pub struct SourcePosition {
     // the line number
@@ -10,6 +16,404 @@ pub struct SourcePosition {
 
 impl Default for SourcePosition {
     fn default() -> Self {
-        SourcePosition { line: 1, col: 0 }
+        SourcePosition { line: 1, col: 1 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+// the different kinds of errors the lexer can report
+pub enum LexErrorKind {
+    // a character that cannot start any token
+    IllegalCharacter(char),
+    // an integer literal that does not fit in the configured integer width
+    NumberTooLarge,
+    // a digit that is not valid in the radix of its integer literal
+    InvalidDigit { digit: char, radix: u32 },
+    // an integer literal without any digits, such as '0x'
+    MissingDigits,
+    // a string literal without a closing '"'
+    UnterminatedString,
+    // a '{' comment without a matching '}'
+    UnterminatedComment,
+    // an unknown escape sequence in a string literal
+    IllegalEscape(char),
+    // a '\x' escape that is not followed by two hexadecimal digits of ASCII
+    InvalidHexEscape,
+    // a '\u' escape that is not of the form '\u{...}' with one to six
+    // hexadecimal digits naming a Unicode scalar value
+    InvalidUnicodeEscape,
+    // an identifier longer than the maximum identifier length
+    IdentifierTooLong,
+    // an ASCII control character inside a string literal
+    NonPrintableInString(u8),
+    // bytes that are not valid UTF-8
+    InvalidUtf8,
+    // the source could not be read
+    Io(String),
+}
+
+impl fmt::Display for LexErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LexErrorKind::IllegalCharacter(ch) if ch.is_ascii() => {
+                write!(f, "illegal character '{}' (ASCII #{})", ch, *ch as u32)
+            }
+            LexErrorKind::IllegalCharacter(ch) => {
+                write!(f, "illegal character '{}' (U+{:04X})", ch, *ch as u32)
+            }
+            LexErrorKind::NumberTooLarge => write!(f, "number too large"),
+            LexErrorKind::InvalidDigit { digit, radix } => {
+                let base = match radix {
+                    2 => "binary",
+                    8 => "octal",
+                    16 => "hexadecimal",
+                    _ => "decimal",
+                };
+                write!(f, "invalid digit '{}' in {} literal", digit, base)
+            }
+            LexErrorKind::MissingDigits => write!(f, "missing digits in number"),
+            LexErrorKind::UnterminatedString => write!(f, "string not closed"),
+            LexErrorKind::UnterminatedComment => write!(f, "comment not closed"),
+            LexErrorKind::IllegalEscape(ch) => write!(f, "illegal escape code '{}' in string", ch),
+            LexErrorKind::InvalidHexEscape => write!(f, "invalid hexadecimal escape in string"),
+            LexErrorKind::InvalidUnicodeEscape => write!(f, "invalid unicode escape in string"),
+            LexErrorKind::IdentifierTooLong => write!(f, "identifier too long"),
+            LexErrorKind::NonPrintableInString(ch) => {
+                write!(f, "non-printable character (ASCII {}) in string", ch)
+            }
+            LexErrorKind::InvalidUtf8 => write!(f, "invalid UTF-8 sequence"),
+            LexErrorKind::Io(message) => write!(f, "could not read the source: {}", message),
+        }
     }
 }
+
+#[derive(Debug, Clone, PartialEq)]
+// an error encountered while lexing
+pub struct LexError {
+    // what went wrong
+    pub kind: LexErrorKind,
+
+    // the part of the source file the error is about
+    pub span: Span,
+
+    // the line and column at which `span` starts
+    pub position: SourcePosition,
+
+    // the name of the source file, if known
+    pub file_name: Option<String>,
+}
+
+// formats the error as "file:line:col: message", leaving out the file name if
+// it is not known
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(file_name) = &self.file_name {
+            write!(f, "{}:", file_name)?;
+        }
+        write!(
+            f,
+            "{}:{}: {}",
+            self.position.line, self.position.col, self.kind
+        )
+    }
+}
+
+impl error::Error for LexError {}
+
+#[derive(Debug, Clone, PartialEq)]
+// the different kinds of errors the parser can report
+pub enum ParseErrorKind {
+    // an error from the lexer, which stops the parser
+    Lex(LexErrorKind),
+    // a token other than the one the grammar requires
+    ExpectedToken {
+        expected: TokenKind,
+        found: TokenKind,
+    },
+    // a token that cannot start the construct the grammar requires, such as
+    // "statement" or "expression"
+    Expected {
+        expected: &'static str,
+        found: TokenKind,
+    },
+    // a relational operator applied to the result of another, as in
+    // "a < b < c"
+    ChainedComparison,
+    // the number literal one past the largest integer, which is only allowed
+    // when negated
+    NumberTooLarge,
+}
+
+// how a token kind is referred to in error messages
+fn describe(kind: TokenKind) -> String {
+    match kind.text() {
+        Some(text) => format!("'{}'", text),
+        None => match kind {
+            TokenKind::Eof => "end of file".to_string(),
+            TokenKind::Id => "identifier".to_string(),
+            TokenKind::Number => "number".to_string(),
+            TokenKind::StringLiteral => "string".to_string(),
+            TokenKind::DocComment => "doc comment".to_string(),
+            _ => "invalid token".to_string(),
+        },
+    }
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorKind::Lex(kind) => write!(f, "{}", kind),
+            ParseErrorKind::ExpectedToken { expected, found } => write!(
+                f,
+                "expected {}, but found {}",
+                describe(*expected),
+                describe(*found)
+            ),
+            ParseErrorKind::Expected { expected, found } => {
+                write!(f, "expected {}, but found {}", expected, describe(*found))
+            }
+            ParseErrorKind::ChainedComparison => {
+                write!(f, "comparison operators cannot be chained")
+            }
+            ParseErrorKind::NumberTooLarge => write!(f, "number too large"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+// an error encountered while parsing
+pub struct ParseError {
+    // what went wrong
+    pub kind: ParseErrorKind,
+
+    // the part of the source file the error is about
+    pub span: Span,
+
+    // the line and column at which `span` starts
+    pub position: SourcePosition,
+
+    // the name of the source file, if known
+    pub file_name: Option<String>,
+}
+
+impl From<LexError> for ParseError {
+    fn from(err: LexError) -> Self {
+        ParseError {
+            kind: ParseErrorKind::Lex(err.kind),
+            span: err.span,
+            position: err.position,
+            file_name: err.file_name,
+        }
+    }
+}
+
+// formats the error like LexError
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(file_name) = &self.file_name {
+            write!(f, "{}:", file_name)?;
+        }
+        write!(
+            f,
+            "{}:{}: {}",
+            self.position.line, self.position.col, self.kind
+        )
+    }
+}
+
+impl error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+// the different kinds of errors name resolution can report, each with the
+// name it is about
+pub enum ResolveErrorKind {
+    // a name used without being declared
+    Undeclared(String),
+    // a name declared twice in the same scope
+    Duplicate { name: String, previous: Span },
+    // a variable or parameter used as a function
+    CallToVariable { name: String, declaration: Span },
+    // a function assigned to, or read into by "get"
+    AssignToFunction { name: String, declaration: Span },
+    // a function used as a variable in an expression
+    FunctionAsVariable { name: String, declaration: Span },
+    // a declaration hiding one of the same name in an enclosing scope, which
+    // is only a warning
+    Shadowing { name: String, shadowed: Span },
+}
+
+impl ResolveErrorKind {
+    pub fn is_warning(&self) -> bool {
+        matches!(self, ResolveErrorKind::Shadowing { .. })
+    }
+}
+
+impl fmt::Display for ResolveErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResolveErrorKind::Undeclared(name) => write!(f, "'{}' is not declared", name),
+            ResolveErrorKind::Duplicate { name, .. } => {
+                write!(f, "'{}' is already declared", name)
+            }
+            ResolveErrorKind::CallToVariable { name, .. } => {
+                write!(f, "'{}' is a variable, not a function", name)
+            }
+            ResolveErrorKind::AssignToFunction { name, .. } => {
+                write!(f, "cannot assign to function '{}'", name)
+            }
+            ResolveErrorKind::FunctionAsVariable { name, .. } => {
+                write!(f, "'{}' is a function, not a variable", name)
+            }
+            ResolveErrorKind::Shadowing { name, .. } => {
+                write!(f, "'{}' shadows an earlier declaration", name)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+// an error encountered while resolving names
+pub struct ResolveError {
+    // what went wrong
+    pub kind: ResolveErrorKind,
+
+    // the name the error is about
+    pub span: Span,
+
+    // the line and column at which `span` starts
+    pub position: SourcePosition,
+
+    // the name of the source file, if known
+    pub file_name: Option<String>,
+}
+
+// formats the error like LexError, with "warning: " before the message of a
+// warning
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(file_name) = &self.file_name {
+            write!(f, "{}:", file_name)?;
+        }
+        write!(f, "{}:{}: ", self.position.line, self.position.col)?;
+        if self.kind.is_warning() {
+            write!(f, "warning: ")?;
+        }
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl error::Error for ResolveError {}
+
+#[derive(Debug, Clone, PartialEq)]
+// the different kinds of errors the type checker can report
+pub enum TypeErrorKind {
+    // an error or warning from name resolution, which the type checker runs
+    // first
+    Resolve(ResolveErrorKind),
+    // an expression of the wrong type
+    Mismatch { expected: Ty, found: Ty },
+    // operands of '=' or '<>' of different types
+    Incomparable { left: Ty, right: Ty },
+    // an index or "array" applied to something other than an array
+    NotAnArray(Ty),
+    // an array read by "get" or written by "put"
+    NotScalar(Ty),
+    // a call with too few or too many arguments
+    WrongArgumentCount { expected: usize, found: usize },
+    // a call of a procedure where a value is needed
+    NoValue(String),
+    // a "leave" without a value in a function returning one
+    MissingValue(Ty),
+    // a "leave" with a value in a procedure or the program body
+    UnexpectedValue,
+    // a function returning a value whose body can end without a "leave"
+    MissingLeave(String),
+    // a string or '.' outside the items of a "put" statement
+    OnlyInPut,
+}
+
+impl TypeErrorKind {
+    pub fn is_warning(&self) -> bool {
+        matches!(self, TypeErrorKind::Resolve(kind) if kind.is_warning())
+    }
+}
+
+impl fmt::Display for TypeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeErrorKind::Resolve(kind) => write!(f, "{}", kind),
+            TypeErrorKind::Mismatch { expected, found } => {
+                write!(f, "expected {}, but found {}", expected, found)
+            }
+            TypeErrorKind::Incomparable { left, right } => {
+                write!(f, "cannot compare {} with {}", left, right)
+            }
+            TypeErrorKind::NotAnArray(ty) => write!(f, "expected an array, but found {}", ty),
+            TypeErrorKind::NotScalar(ty) => {
+                write!(f, "expected integer or boolean, but found {}", ty)
+            }
+            TypeErrorKind::WrongArgumentCount { expected, found } => {
+                write!(f, "expected {} arguments, but found {}", expected, found)
+            }
+            TypeErrorKind::NoValue(name) => write!(f, "'{}' does not return a value", name),
+            TypeErrorKind::MissingValue(ty) => {
+                write!(f, "'leave' needs a value of type {}", ty)
+            }
+            TypeErrorKind::UnexpectedValue => {
+                write!(
+                    f,
+                    "'leave' can only have a value in a function returning one"
+                )
+            }
+            TypeErrorKind::MissingLeave(name) => {
+                write!(f, "function '{}' can end without leaving a value", name)
+            }
+            TypeErrorKind::OnlyInPut => {
+                write!(f, "strings and '.' are only allowed in 'put'")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+// an error encountered while type checking
+pub struct TypeError {
+    // what went wrong
+    pub kind: TypeErrorKind,
+
+    // the part of the source file the error is about
+    pub span: Span,
+
+    // the line and column at which `span` starts
+    pub position: SourcePosition,
+
+    // the name of the source file, if known
+    pub file_name: Option<String>,
+}
+
+impl From<ResolveError> for TypeError {
+    fn from(err: ResolveError) -> Self {
+        TypeError {
+            kind: TypeErrorKind::Resolve(err.kind),
+            span: err.span,
+            position: err.position,
+            file_name: err.file_name,
+        }
+    }
+}
+
+// formats the error like ResolveError
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(file_name) = &self.file_name {
+            write!(f, "{}:", file_name)?;
+        }
+        write!(f, "{}:{}: ", self.position.line, self.position.col)?;
+        if self.kind.is_warning() {
+            write!(f, "warning: ")?;
+        }
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl error::Error for TypeError {}
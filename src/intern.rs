@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+// a handle to a string stored in an Interner. Two symbols from the same
+// interner are equal exactly when their strings are.
+pub struct Symbol(u32);
+
+impl Symbol {
+    pub fn as_u32(self) -> u32 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+// stores every distinct identifier and string literal once, handing out a
+// Symbol for each so that names can be compared and hashed in O(1)
+pub struct Interner {
+    // the symbol of every string interned so far
+    symbols: HashMap<Rc<str>, Symbol>,
+
+    // the string of every symbol, indexed by the symbol
+    strings: Vec<Rc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Interner::default()
+    }
+
+    // the symbol for `string`, adding it to the interner if it is new
+    pub fn intern(&mut self, string: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(string) {
+            return *symbol;
+        }
+
+        let symbol = Symbol(u32::try_from(self.strings.len()).expect("too many interned strings"));
+        let string: Rc<str> = Rc::from(string);
+        self.strings.push(Rc::clone(&string));
+        self.symbols.insert(string, symbol);
+        symbol
+    }
+
+    // the symbol for `string`, if it has been interned
+    pub fn get(&self, string: &str) -> Option<Symbol> {
+        self.symbols.get(string).copied()
+    }
+
+    // the string of a symbol handed out by this interner
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.strings[symbol.0 as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern_and_resolve() {
+        let mut interner = Interner::new();
+
+        let x = interner.intern("x");
+        let y = interner.intern("y");
+        assert_ne!(x, y);
+        assert_eq!(interner.intern("x"), x);
+        assert_eq!(interner.get("y"), Some(y));
+        assert_eq!(interner.get("z"), None);
+        assert_eq!(interner.resolve(y), "y");
+        assert_eq!(interner.len(), 2);
+    }
+}
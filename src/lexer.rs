@@ -1,305 +1,916 @@
-use crate::error::SourcePosition;
-use crate::token::{create_token_from_reserved_words_index, Token, MAX_ID_LENGTH, RESERVED_WORDS};
+use std::collections::VecDeque;
+use std::io::BufRead;
 
-pub struct Lexer<'a> {
-    // the source file contents
-    bytes: &'a [u8],
+use crate::error::{LexError, LexErrorKind, SourcePosition};
+use crate::intern::Interner;
+use crate::source::{ReaderSource, Source};
+use crate::span::{multibyte_char_width, utf8_char_width, SourceMap, Span, DEFAULT_TAB_WIDTH};
+use crate::token::{
+    keyword, LosslessToken, Token, TokenKind, TokenValue, Trivia, TriviaKind, MAX_ID_LENGTH,
+};
 
-    // the next byte in the source
+// receives every token (and error) the lexer produces, for tracing and
+// debugging the lexer without touching its output
+pub trait LexerObserver {
+    fn on_token(&mut self, token: &Token, position: SourcePosition);
+
+    fn on_error(&mut self, _err: &LexError) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+// the integer type number literals are mapped to on the JVM
+pub enum IntegerWidth {
+    // a 32-bit `int`, the default
+    I32,
+    // a 64-bit `long`
+    I64,
+}
+
+impl IntegerWidth {
+    // the largest value a number literal may have. Literals are never negative,
+    // so this is one more than the largest value of the type, which allows its
+    // smallest value to be written as a negated literal. The lexer only accepts
+    // the literal right after a '-', and the parser rejects it when that '-'
+    // does not negate it.
+    pub fn max_magnitude(&self) -> u64 {
+        match self {
+            IntegerWidth::I32 => i32::MAX as u64 + 1,
+            IntegerWidth::I64 => i64::MAX as u64 + 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+// the settings a lexer can be configured with, so that dialects of the
+// language (and tests) do not need to edit constants
+pub struct LexerOptions {
+    // the maximum number of characters in an identifier
+    pub max_id_length: usize,
+
+    // the width of the integer type number literals must fit in
+    pub integer_width: IntegerWidth,
+
+    // whether identifiers may contain Unicode letters and digits, see
+    // Lexer::with_unicode_identifiers
+    pub unicode_identifiers: bool,
+
+    // the number of columns between tab stops when computing columns
+    pub tab_width: usize,
+
+    // whether whitespace and comments are recorded as trivia, which is kept
+    // until Lexer::take_trivia is called
+    pub retain_trivia: bool,
+}
+
+impl Default for LexerOptions {
+    fn default() -> Self {
+        LexerOptions {
+            max_id_length: MAX_ID_LENGTH,
+            integer_width: IntegerWidth::I32,
+            unicode_identifiers: false,
+            tab_width: DEFAULT_TAB_WIDTH,
+            retain_trivia: false,
+        }
+    }
+}
+
+// a lexer over source bytes, which by default are a slice holding the whole
+// source file, see Lexer::from_reader for reading from a stream instead
+pub struct Lexer<'a, S = &'a [u8]> {
+    // where the source bytes come from
+    source: S,
+
+    // the next byte in the source, or '\0' once the end has been reached
     ch: u8,
 
-    // the last byte that was read from the source
-    last_read: u8,
+    // whether the end of the source has been reached
+    eof: bool,
 
-    // the current index in the source
+    // the byte offset of `ch` in the source
     index: usize,
 
-    // the current column number
-    column_number: usize,
+    // the byte offset at which the current token starts
+    start: usize,
+
+    // the start of every line seen so far
+    source_map: SourceMap,
+
+    // the name of the source file, used when reporting errors
+    file_name: Option<String>,
+
+    // where the names of identifiers and the values of string literals are
+    // stored
+    interner: Interner,
+
+    // the settings the lexer was configured with
+    options: LexerOptions,
+
+    // the trivia recorded since the last token
+    trivia: Vec<Trivia>,
+
+    // whether errors are recorded and turned into TokenKind::Error instead of
+    // stopping the lexer
+    recover: bool,
+
+    // the errors recorded so far in recovery mode
+    errors: Vec<LexError>,
+
+    // notified of every token and error, if set
+    observer: Option<Box<dyn LexerObserver + 'a>>,
+
+    // tokens that have been peeked at but not consumed yet
+    lookahead: VecDeque<Result<Token, LexError>>,
+
+    // the offset of the oldest checkpoint still in use, which keeps the source
+    // from discarding the bytes after it
+    oldest_checkpoint: Option<usize>,
+
+    // whether the lexer has produced TokenKind::Eof or an error
+    done: bool,
 
-    // a place (or position) in the source file
-    position: SourcePosition,
+    // the kind of the last token read, as a number literal one past the
+    // largest integer is only allowed after a '-'
+    previous: TokenKind,
+}
+
+// the saved state of a lexer, see Lexer::checkpoint
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    ch: u8,
+    eof: bool,
+    index: usize,
+    start: usize,
+    trivia: Vec<Trivia>,
+    error_count: usize,
+    lookahead: VecDeque<Result<Token, LexError>>,
+    done: bool,
+    previous: TokenKind,
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(bytes: &'a [u8]) -> Self {
-        let initial_ch = if bytes.is_empty() { b'\0' } else { bytes[0] };
+        Lexer::with_source(bytes)
+    }
+
+    pub fn new_with_options(bytes: &'a [u8], options: LexerOptions) -> Self {
+        Lexer::with_source(bytes).with_options(options)
+    }
+}
+
+impl<R: BufRead> Lexer<'_, ReaderSource<R>> {
+    // a lexer that pulls the source from `reader` as it goes, keeping only the
+    // bytes of the current token (and of any peeked tokens and checkpoints) in
+    // memory. The source map still grows with the input, by an entry for every
+    // line, tab and multibyte character, so that positions can be found for
+    // any span later on.
+    pub fn from_reader(reader: R) -> Self {
+        Lexer::with_source(ReaderSource::new(reader))
+    }
+}
+
+impl<'a, S: Source> Lexer<'a, S> {
+    pub fn with_source(mut source: S) -> Self {
+        let first = source.byte_at(0);
         Lexer {
-            bytes,
-            ch: initial_ch,
-            last_read: initial_ch,
+            source,
+            ch: first.unwrap_or(b'\0'),
+            eof: first.is_none(),
             index: 0,
-            column_number: 0,
-            position: SourcePosition::default(),
+            start: 0,
+            source_map: SourceMap::new(),
+            file_name: None,
+            interner: Interner::new(),
+            options: LexerOptions::default(),
+            trivia: Vec::new(),
+            recover: false,
+            errors: Vec::new(),
+            observer: None,
+            lookahead: VecDeque::new(),
+            oldest_checkpoint: None,
+            done: false,
+            previous: TokenKind::Eof,
         }
     }
 
+    // reads the next token into `token`. Kept for compatibility, new code should
+    // iterate over the lexer instead.
     pub fn get_token(&mut self, token: &mut Token) -> Result<(), String> {
-        self.skip_whitespace();
+        self.next_token()
+            .map(|next| *token = next)
+            .map_err(|err| err.kind.to_string())
+    }
 
-        println!(
-            "{} / {} [{}]",
-            self.index,
-            self.bytes.len(),
-            self.bytes[self.index] as char
-        );
+    // replaces all of the lexer's settings. This must be done before any token
+    // is read, as the source map depends on the tab width.
+    pub fn with_options(mut self, options: LexerOptions) -> Self {
+        self.source_map = SourceMap::new().with_tab_width(options.tab_width);
+        self.options = options;
+        self
+    }
 
-        // remember token start
-        self.position.col = self.column_number;
-
-        if self.ch.is_ascii_alphabetic() || self.ch == b'_' {
-            self.process_word(token)?;
-        } else if self.ch.is_ascii_digit() {
-            self.process_number(token)?;
-        } else {
-            match self.ch {
-                b'"' => {
-                    self.position.col = self.column_number;
-                    self.next_char();
-                    self.process_string(token)?;
-                }
-                b'{' => {
-                    self.next_char();
-                    self.skip_comment(token)?;
-                    self.get_token(token)?;
-                }
-                b']' => {
-                    *token = Token::CloseBracket;
-                    self.next_char();
-                }
-                b')' => {
-                    *token = Token::CloseParenthesis;
-                    self.next_char();
-                }
-                b',' => {
-                    *token = Token::Comma;
-                    self.next_char();
-                }
-                b'/' => {
-                    *token = Token::Divide;
-                    self.next_char();
-                }
-                b'.' => {
-                    *token = Token::Concatenate;
-                    self.next_char();
-                }
-                b'=' => {
-                    *token = Token::Equal;
-                    self.next_char();
-                }
-                b'[' => {
-                    *token = Token::OpenBracket;
-                    self.next_char();
-                }
-                b'*' => {
-                    *token = Token::Multiply;
-                    self.next_char();
-                }
-                b'+' => {
-                    *token = Token::Plus;
-                    self.next_char();
-                }
-                b';' => {
-                    *token = Token::Semicolon;
-                    self.next_char();
-                }
-                b'-' => {
-                    *token = Token::Minus;
-                    self.next_char();
-                }
-                b'(' => {
-                    *token = Token::OpenParenthesis;
+    // the settings the lexer was configured with
+    pub fn options(&self) -> &LexerOptions {
+        &self.options
+    }
+
+    // sets the file name reported in errors
+    pub fn with_file_name(mut self, file_name: &str) -> Self {
+        self.file_name = Some(file_name.to_string());
+        self
+    }
+
+    // the file name reported in errors, if set
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    // turns on recovery mode, in which a lexical error is recorded (see
+    // `errors`) and reported as TokenKind::Error, after which lexing carries on from
+    // the next plausible token boundary
+    pub fn with_recovery(mut self, recover: bool) -> Self {
+        self.recover = recover;
+        self
+    }
+
+    // allows identifiers to start with any Unicode letter and continue with any
+    // Unicode letter or digit, as an approximation of the XID_Start and
+    // XID_Continue properties. Keywords remain ASCII.
+    pub fn with_unicode_identifiers(mut self, unicode_identifiers: bool) -> Self {
+        self.options.unicode_identifiers = unicode_identifiers;
+        self
+    }
+
+    // sets the width of the integer type number literals must fit in
+    pub fn with_integer_width(mut self, integer_width: IntegerWidth) -> Self {
+        self.options.integer_width = integer_width;
+        self
+    }
+
+    // makes the lexer record whitespace and comments as trivia, see
+    // Lexer::take_trivia and Lexer::lossless
+    pub fn with_trivia(mut self, retain_trivia: bool) -> Self {
+        self.options.retain_trivia = retain_trivia;
+        self
+    }
+
+    // sets the observer that is notified of every token and error
+    pub fn with_observer(mut self, observer: Box<dyn LexerObserver + 'a>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    // the errors recorded so far in recovery mode
+    pub fn errors(&self) -> &[LexError] {
+        &self.errors
+    }
+
+    // removes and returns the errors recorded so far in recovery mode
+    pub fn take_errors(&mut self) -> Vec<LexError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    // makes the lexer add to `interner`, so that symbols are shared with
+    // whatever else uses it
+    pub fn with_interner(mut self, interner: Interner) -> Self {
+        self.interner = interner;
+        self
+    }
+
+    // the interner holding the symbols of the tokens produced so far
+    pub fn interner(&self) -> &Interner {
+        &self.interner
+    }
+
+    // hands the interner over to the next stage of the compiler
+    pub fn into_interner(self) -> Interner {
+        self.interner
+    }
+
+    // the line starts of the source consumed so far, which is the whole file
+    // once TokenKind::Eof has been produced
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    pub fn next_token(&mut self) -> Result<Token, LexError> {
+        match self.lookahead.pop_front() {
+            Some(result) => result,
+            None => self.lex_token(),
+        }
+    }
+
+    // the next token, without consuming it
+    pub fn peek(&mut self) -> Result<&Token, &LexError> {
+        self.peek_nth(0)
+    }
+
+    // the token `n` tokens ahead (0 being the next one), without consuming any.
+    // Peeking past TokenKind::Eof (or an error outside of recovery mode) gives that
+    // token (or error) again.
+    pub fn peek_nth(&mut self, n: usize) -> Result<&Token, &LexError> {
+        while self.lookahead.len() <= n
+            && !matches!(
+                self.lookahead.back(),
+                Some(
+                    Ok(Token {
+                        kind: TokenKind::Eof,
+                        ..
+                    }) | Err(_)
+                )
+            )
+        {
+            let result = self.lex_token();
+            self.lookahead.push_back(result);
+        }
+
+        let index = n.min(self.lookahead.len() - 1);
+        self.lookahead[index].as_ref()
+    }
+
+    // saves the state of the lexer, so that it can be restored to continue
+    // from the current token again. The source keeps the bytes after the
+    // oldest checkpoint until `release_checkpoints` is called.
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        self.oldest_checkpoint = Some(self.oldest_checkpoint.unwrap_or(self.index));
+        Checkpoint {
+            ch: self.ch,
+            eof: self.eof,
+            index: self.index,
+            start: self.start,
+            trivia: self.trivia.clone(),
+            error_count: self.errors.len(),
+            lookahead: self.lookahead.clone(),
+            done: self.done,
+            previous: self.previous,
+        }
+    }
+
+    // rewinds the lexer to a checkpoint taken earlier. Errors recorded in
+    // recovery mode since the checkpoint are discarded.
+    pub fn restore(&mut self, checkpoint: &Checkpoint) {
+        assert!(checkpoint.index <= self.index);
+
+        self.ch = checkpoint.ch;
+        self.eof = checkpoint.eof;
+        self.index = checkpoint.index;
+        self.start = checkpoint.start;
+        self.source_map.truncate(checkpoint.index);
+        self.trivia = checkpoint.trivia.clone();
+        self.errors.truncate(checkpoint.error_count);
+        self.lookahead = checkpoint.lookahead.clone();
+        self.done = checkpoint.done;
+        self.previous = checkpoint.previous;
+    }
+
+    // tells the lexer that none of the checkpoints taken so far will be
+    // restored, so the source may free the bytes they kept
+    pub fn release_checkpoints(&mut self) {
+        self.oldest_checkpoint = None;
+    }
+
+    // the offset of the first byte that may still be asked for: the bytes of
+    // the tokens waiting in `lookahead` and of the oldest checkpoint are kept,
+    // as well as those of the token about to be read
+    fn keep_from(&self) -> usize {
+        let lookahead = self.lookahead.front().map(|result| match result {
+            Ok(token) => token.span.start,
+            Err(err) => err.span.start,
+        });
+        [self.oldest_checkpoint, lookahead]
+            .into_iter()
+            .flatten()
+            .fold(self.index, usize::min)
+    }
+
+    fn lex_token(&mut self) -> Result<Token, LexError> {
+        self.source.discard_before(self.keep_from());
+
+        let mut token = Token::default();
+        let mut result = self.scan_token(&mut token);
+        if let Some(err) = self.source.take_error() {
+            result = Err(self.error(
+                LexErrorKind::Io(err.to_string()),
+                Span::new(self.index, self.index),
+            ));
+        }
+
+        if let Err(err) = result {
+            if let Some(observer) = &mut self.observer {
+                observer.on_error(&err);
+            }
+
+            if !self.recover {
+                return Err(err);
+            }
+
+            self.synchronize(&err.kind);
+            self.errors.push(err);
+            token = Token::new(TokenKind::Error, Span::default());
+        }
+
+        token.span = Span::new(self.start, self.index);
+        self.previous = token.kind;
+        if let Some(observer) = &mut self.observer {
+            observer.on_token(&token, self.source_map.position(token.span.start));
+        }
+        Ok(token)
+    }
+
+    // skips past the text that caused an error of the given kind, so that the
+    // next token starts at a plausible boundary
+    fn synchronize(&mut self, kind: &LexErrorKind) {
+        match kind {
+            // the whole lexeme (or the rest of the file) has already been
+            // consumed, or the source cannot be read any further
+            LexErrorKind::Io(_)
+            | LexErrorKind::NumberTooLarge
+            | LexErrorKind::InvalidDigit { .. }
+            | LexErrorKind::MissingDigits
+            | LexErrorKind::UnterminatedString
+            | LexErrorKind::UnterminatedComment
+            | LexErrorKind::IdentifierTooLong => (),
+            // give up on the rest of the string literal
+            _ if self.source.byte_at(self.start) == Some(b'"') => {
+                while !self.at_end() && self.ch != b'"' {
                     self.next_char();
                 }
-                b':' => {
+                self.next_char();
+            }
+            // the offending character has not been consumed yet, unless it was
+            // the ':' of an incomplete ':='
+            _ => {
+                if self.index == self.start {
                     self.next_char();
-                    if self.ch == b'=' {
-                        *token = Token::Gets;
+                    while is_utf8_continuation(self.ch) {
                         self.next_char();
-                    } else {
-                        self.position.col = self.column_number - 1;
-                        return Err(format!("illegal character ':' (ASCII #{})", b':'));
                     }
                 }
-                b'>' => {
-                    self.next_char();
-                    if self.ch == b'=' {
-                        *token = Token::GreaterEqual;
+            }
+        }
+    }
+
+    fn scan_token(&mut self, token: &mut Token) -> Result<(), LexError> {
+        // comments are skipped by starting over after them, rather than by
+        // recursing, so that any number of them in a row is fine
+        loop {
+            self.skip_whitespace();
+
+            // remember token start
+            self.start = self.index;
+
+            if self.ch.is_ascii_alphabetic() || self.ch == b'_' || self.at_unicode_letter() {
+                self.process_word(token)?;
+            } else if self.ch.is_ascii_digit() {
+                self.process_number(token)?;
+            } else {
+                match self.ch {
+                    b'"' => {
                         self.next_char();
-                    } else {
-                        *token = Token::GreaterThan;
+                        self.process_string(token)?;
                     }
-                }
-                b'<' => {
-                    self.next_char();
-                    match self.ch {
-                        b'=' => {
-                            *token = Token::LessEqual;
+                    b'{' => {
+                        self.next_char();
+                        if self.ch == b'!' {
                             self.next_char();
+                            self.skip_comment(self.start)?;
+                            self.process_doc_comment(token);
+                        } else {
+                            self.skip_comment(self.start)?;
+                            self.push_trivia(TriviaKind::Comment, self.start);
+                            continue;
                         }
-                        b'>' => {
-                            *token = Token::NotEqual;
+                    }
+                    b']' => {
+                        token.kind = TokenKind::CloseBracket;
+                        self.next_char();
+                    }
+                    b')' => {
+                        token.kind = TokenKind::CloseParenthesis;
+                        self.next_char();
+                    }
+                    b',' => {
+                        token.kind = TokenKind::Comma;
+                        self.next_char();
+                    }
+                    b'/' => {
+                        self.next_char();
+                        if self.ch == b'/' {
+                            while !self.at_end() && self.ch != b'\n' && self.ch != b'\r' {
+                                self.next_char();
+                            }
+                            self.push_trivia(TriviaKind::Comment, self.start);
+                            continue;
+                        } else {
+                            token.kind = TokenKind::Divide;
+                        }
+                    }
+                    b'.' => {
+                        token.kind = TokenKind::Concatenate;
+                        self.next_char();
+                    }
+                    b'=' => {
+                        token.kind = TokenKind::Equal;
+                        self.next_char();
+                    }
+                    b'[' => {
+                        token.kind = TokenKind::OpenBracket;
+                        self.next_char();
+                    }
+                    b'*' => {
+                        token.kind = TokenKind::Multiply;
+                        self.next_char();
+                    }
+                    b'+' => {
+                        token.kind = TokenKind::Plus;
+                        self.next_char();
+                    }
+                    b';' => {
+                        token.kind = TokenKind::Semicolon;
+                        self.next_char();
+                    }
+                    b'-' => {
+                        token.kind = TokenKind::Minus;
+                        self.next_char();
+                    }
+                    b'(' => {
+                        token.kind = TokenKind::OpenParenthesis;
+                        self.next_char();
+                    }
+                    b':' => {
+                        self.next_char();
+                        if self.ch == b'=' {
+                            token.kind = TokenKind::Gets;
                             self.next_char();
+                        } else {
+                            return Err(self.error(
+                                LexErrorKind::IllegalCharacter(':'),
+                                Span::new(self.start, self.start + 1),
+                            ));
                         }
-                        _ => *token = Token::LessThan,
                     }
-                }
-                _ => {
-                    self.position.col = self.column_number;
-                    if !self.has_next_char() {
-                        *token = Token::Eof;
-                    } else {
-                        return Err(format!(
-                            "illegal character '{}' (ASCII #{})",
-                            self.ch as char, self.ch,
-                        ));
+                    b'>' => {
+                        self.next_char();
+                        if self.ch == b'=' {
+                            token.kind = TokenKind::GreaterEqual;
+                            self.next_char();
+                        } else {
+                            token.kind = TokenKind::GreaterThan;
+                        }
+                    }
+                    b'<' => {
+                        self.next_char();
+                        match self.ch {
+                            b'=' => {
+                                token.kind = TokenKind::LessEqual;
+                                self.next_char();
+                            }
+                            b'>' => {
+                                token.kind = TokenKind::NotEqual;
+                                self.next_char();
+                            }
+                            _ => token.kind = TokenKind::LessThan,
+                        }
+                    }
+                    _ => {
+                        if self.at_end() {
+                            token.kind = TokenKind::Eof;
+                        } else {
+                            let ch = self.peek_char()?;
+                            return Err(self.error(
+                                LexErrorKind::IllegalCharacter(ch),
+                                Span::new(self.index, self.index + ch.len_utf8()),
+                            ));
+                        }
                     }
                 }
             }
+            return Ok(());
+        }
+    }
+
+    fn error(&self, kind: LexErrorKind, span: Span) -> LexError {
+        LexError {
+            kind,
+            span,
+            position: self.source_map.position(span.start),
+            file_name: self.file_name.clone(),
         }
-        Ok(())
     }
 
     fn next_char(&mut self) {
-        if !self.has_next_char() {
-            self.ch = b'\0';
+        if self.at_end() {
             return;
         }
 
-        self.index += 1;
-        self.ch = self.bytes[self.index];
+        // "\r\n" is a single newline, its line starting after the '\n'
+        if self.ch == b'\n'
+            || (self.ch == b'\r' && self.source.byte_at(self.index + 1) != Some(b'\n'))
+        {
+            self.source_map.add_line_start(self.index + 1);
+        } else if self.ch == b'\t' {
+            self.source_map.add_tab(self.index);
+        } else if self.ch >= 0xC0 {
+            // the bytes of the character, as far as the source has them
+            let mut bytes = [0; 4];
+            let mut length = 0;
+            while length < utf8_char_width(self.ch) {
+                match self.source.byte_at(self.index + length) {
+                    Some(byte) => bytes[length] = byte,
+                    None => break,
+                }
+                length += 1;
+            }
+            if let Some(width) = multibyte_char_width(&bytes[..length]) {
+                self.source_map.add_multibyte_char(self.index, width);
+            }
+        }
 
-        self.column_number += 1;
-        if self.last_read == b'\n' {
-            self.position.line += 1;
-            self.column_number = 1;
+        self.index += 1;
+        match self.source.byte_at(self.index) {
+            Some(ch) => self.ch = ch,
+            None => {
+                self.ch = b'\0';
+                self.eof = true;
+            }
         }
-        self.last_read = self.ch;
     }
 
     #[inline(always)]
-    fn has_next_char(&self) -> bool {
-        self.index + 1 < self.bytes.len()
+    fn at_end(&self) -> bool {
+        self.eof
     }
 
-    fn process_number(&mut self, token: &mut Token) -> Result<(), String> {
-        let mut final_value = 0;
+    // the byte after `ch`, or '\0' if there is none
+    fn peek_next(&mut self) -> u8 {
+        self.source.byte_at(self.index + 1).unwrap_or(b'\0')
+    }
 
-        self.position.col = self.column_number;
+    // decodes the (possibly multibyte) character starting at `ch` without
+    // consuming it
+    fn peek_char(&mut self) -> Result<char, LexError> {
+        let width = utf8_char_width(self.ch);
+        let available = (0..width)
+            .take_while(|offset| self.source.byte_at(self.index + offset).is_some())
+            .count();
 
-        while self.ch.is_ascii_digit() {
-            let digit = (self.ch as char)
-                .to_digit(10)
-                .expect("Could not convert to a digit") as i32;
+        match std::str::from_utf8(self.source.slice(self.index, self.index + available)) {
+            Ok(value) if width > 0 && available == width => Ok(value.chars().next().unwrap()),
+            _ => Err(self.error(
+                LexErrorKind::InvalidUtf8,
+                Span::new(self.index, self.index + 1),
+            )),
+        }
+    }
 
-            if final_value <= ((i32::MAX - digit) / 10) {
-                final_value = final_value * 10 + digit;
+    // consumes the character starting at `ch`, which is `width` bytes long
+    fn skip_char(&mut self, width: usize) {
+        for _ in 0..width {
+            self.next_char();
+        }
+    }
 
-                if !self.has_next_char() {
-                    break;
+    fn at_unicode_letter(&mut self) -> bool {
+        self.options.unicode_identifiers
+            && !self.ch.is_ascii()
+            && self.peek_char().is_ok_and(|ch| ch.is_alphabetic())
+    }
+
+    // reads a decimal literal or, with a '0x', '0b' or '0o' prefix, a
+    // hexadecimal, binary or octal one, in which '_' may separate digits
+    fn process_number(&mut self, token: &mut Token) -> Result<(), LexError> {
+        let radix = match (self.ch, self.peek_next()) {
+            (b'0', b'x') => 16,
+            (b'0', b'b') => 2,
+            (b'0', b'o') => 8,
+            _ => 10,
+        };
+        if radix != 10 {
+            self.skip_char(2);
+        }
+
+        let mut final_value: u64 = 0;
+        let mut digits = 0;
+        let mut too_large = false;
+        let mut invalid_digit = None;
+
+        loop {
+            let digit = match self.ch {
+                b'_' => {
+                    self.next_char();
+                    continue;
                 }
+                ch if radix == 16 => (ch as char).to_digit(16),
+                // decimal digits are consumed for every radix so that the error
+                // can point at the offending one
+                ch => (ch as char).to_digit(10),
+            };
+            let Some(digit) = digit else {
+                break;
+            };
 
-                self.next_char();
-            } else {
-                return Err("number too large".to_string());
+            if digit >= radix && invalid_digit.is_none() {
+                invalid_digit = Some(self.index);
+            }
+
+            match final_value
+                .checked_mul(radix as u64)
+                .and_then(|value| value.checked_add(digit as u64))
+            {
+                Some(value) => final_value = value,
+                None => too_large = true,
             }
+
+            digits += 1;
+            self.next_char();
+        }
+
+        if let Some(index) = invalid_digit {
+            return Err(self.error(
+                LexErrorKind::InvalidDigit {
+                    digit: self.source.slice(index, index + 1)[0] as char,
+                    radix,
+                },
+                Span::new(index, index + 1),
+            ));
+        }
+
+        let span = Span::new(self.start, self.index);
+        if digits == 0 {
+            return Err(self.error(LexErrorKind::MissingDigits, span));
+        }
+
+        let max_magnitude = self.options.integer_width.max_magnitude();
+        if too_large
+            || final_value > max_magnitude
+            || (final_value == max_magnitude && self.previous != TokenKind::Minus)
+        {
+            return Err(self.error(LexErrorKind::NumberTooLarge, span));
         }
 
-        *token = Token::Number(final_value);
+        token.kind = TokenKind::Number;
+        token.value = TokenValue::Number(final_value);
         Ok(())
     }
 
-    fn process_string(&mut self, token: &mut Token) -> Result<(), String> {
+    fn process_string(&mut self, token: &mut Token) -> Result<(), LexError> {
         let mut string_literal = String::default();
 
-        loop {
+        while !self.at_end() {
             if self.ch == b'"' {
-                *token = Token::StringLiteral(string_literal);
-                if self.has_next_char() {
-                    self.next_char();
-                }
+                token.kind = TokenKind::StringLiteral;
+                token.value = TokenValue::Symbol(self.interner.intern(&string_literal));
+                self.next_char();
                 return Ok(());
             }
 
             if !self.ch.is_ascii() {
-                // force token start
-                self.position.col = self.column_number;
-                return Err(format!(
-                    "non-printable character (ASCII {}) in string",
-                    self.ch
+                let ch = self.peek_char()?;
+                string_literal.push(ch);
+                self.skip_char(ch.len_utf8());
+            } else if self.ch.is_ascii_control() {
+                return Err(self.error(
+                    LexErrorKind::NonPrintableInString(self.ch),
+                    Span::new(self.index, self.index + 1),
                 ));
             } else if self.ch == b'\\' {
+                let escape_start = self.index;
+                self.next_char();
+                if self.at_end() {
+                    break;
+                }
+                string_literal.push(self.process_escape(escape_start)?);
+            } else {
+                string_literal.push(self.ch as char);
                 self.next_char();
+            }
+        }
 
-                match self.ch {
-                    b'n' | b't' | b'"' => (),
-                    b'\\' => string_literal.push('\\'),
-                    _ => {
-                        // force token start
-                        self.position.col = self.column_number;
-                        return Err(format!(
-                            "illegal escape code '{}' in string",
-                            self.ch as char
-                        ));
+        Err(self.error(
+            LexErrorKind::UnterminatedString,
+            Span::new(self.start, self.index),
+        ))
+    }
+
+    // decodes the escape sequence whose '\\' is at `escape_start` and has already
+    // been consumed, leaving `ch` just past the sequence
+    fn process_escape(&mut self, escape_start: usize) -> Result<char, LexError> {
+        // every escape is ASCII, so only the error needs the whole character
+        if !self.ch.is_ascii() {
+            let escaped = self.peek_char()?;
+            self.skip_char(escaped.len_utf8());
+            return Err(self.error(
+                LexErrorKind::IllegalEscape(escaped),
+                Span::new(escape_start, self.index),
+            ));
+        }
+
+        let escaped = self.ch;
+        self.next_char();
+
+        match escaped {
+            b'n' => Ok('\n'),
+            b't' => Ok('\t'),
+            b'r' => Ok('\r'),
+            b'0' => Ok('\0'),
+            b'"' => Ok('"'),
+            b'\\' => Ok('\\'),
+            b'x' => {
+                // exactly two hexadecimal digits, limited to ASCII
+                let mut value = 0;
+                for _ in 0..2 {
+                    match (self.ch as char).to_digit(16) {
+                        Some(digit) => value = value * 16 + digit,
+                        None => break,
                     }
+                    self.next_char();
                 }
+
+                let span = Span::new(escape_start, self.index);
+                if span.len() != 4 || value > 0x7F {
+                    return Err(self.error(LexErrorKind::InvalidHexEscape, span));
+                }
+                Ok(char::from(value as u8))
             }
+            b'u' => {
+                // one to six hexadecimal digits between braces, naming a Unicode
+                // scalar value
+                let mut value: u32 = 0;
+                let mut digits = 0;
+                let closed = if self.ch == b'{' {
+                    self.next_char();
+                    while let Some(digit) = (self.ch as char).to_digit(16) {
+                        value = value.saturating_mul(16).saturating_add(digit);
+                        digits += 1;
+                        self.next_char();
+                    }
+                    self.ch == b'}'
+                } else {
+                    false
+                };
 
-            string_literal.push(self.ch as char);
+                if closed {
+                    self.next_char();
+                }
 
-            if !self.has_next_char() {
-                break;
+                let span = Span::new(escape_start, self.index);
+                match char::from_u32(value) {
+                    Some(decoded) if closed && (1..=6).contains(&digits) => Ok(decoded),
+                    _ => Err(self.error(LexErrorKind::InvalidUnicodeEscape, span)),
+                }
             }
-
-            self.next_char();
+            _ => Err(self.error(
+                LexErrorKind::IllegalEscape(escaped as char),
+                Span::new(escape_start, self.index),
+            )),
         }
-
-        Err("string not closed".to_string())
     }
 
-    fn process_word(&mut self, token: &mut Token) -> Result<(), String> {
-        self.position.col = self.column_number;
-
-        let start = self.index;
+    fn process_word(&mut self, token: &mut Token) -> Result<(), LexError> {
+        let is_alphanum_or_lodash = |ch: char| ch.is_alphanumeric() || ch == '_';
         let mut id_length = 0;
 
-        let is_alphanum_or_lodash = |ch: &u8| ch.is_ascii_alphanumeric() || *ch == b'_';
-
         loop {
-            self.next_char();
-            id_length += 1;
-
-            if !(is_alphanum_or_lodash(&self.ch) && id_length < MAX_ID_LENGTH) {
+            if self.ch.is_ascii_alphanumeric() || self.ch == b'_' {
+                self.next_char();
+            } else if self.options.unicode_identifiers && !self.ch.is_ascii() {
+                match self.peek_char() {
+                    Ok(ch) if is_alphanum_or_lodash(ch) => self.skip_char(ch.len_utf8()),
+                    _ => break,
+                }
+            } else {
                 break;
             }
+            id_length += 1;
         }
 
-        if is_alphanum_or_lodash(&self.ch) && id_length == MAX_ID_LENGTH {
-            return Err("identifier too long".to_string());
+        let span = Span::new(self.start, self.index);
+        if id_length > self.options.max_id_length {
+            return Err(self.error(LexErrorKind::IdentifierTooLong, span));
         }
 
-        let lexeme = match std::str::from_utf8(&self.bytes[start..start + id_length]) {
+        let lexeme = match std::str::from_utf8(self.source.slice(span.start, span.end)) {
             Ok(value) => value,
-            Err(err) => return Err(format!("Invalid UTF-8 sequence {}", err)),
+            Err(_) => return Err(self.error(LexErrorKind::InvalidUtf8, span)),
         };
 
-        match RESERVED_WORDS.binary_search_by_key(&lexeme, |(raw_str, _)| raw_str) {
-            Ok(index) => *token = create_token_from_reserved_words_index(index),
-            Err(_) => *token = Token::Id(String::from(lexeme)),
+        match keyword(lexeme) {
+            Some(kind) => token.kind = kind,
+            None => {
+                token.kind = TokenKind::Id;
+                token.value = TokenValue::Symbol(self.interner.intern(lexeme));
+            }
         };
         Ok(())
     }
 
-    fn skip_comment(&mut self, token: &mut Token) -> Result<(), String> {
-        // remember the entire position
-        self.position.col = self.column_number - 1;
-
-        let start_pos = self.position;
-
-        while self.has_next_char() {
+    // skips a (possibly nested) comment whose opening '{' is at `start` and has
+    // already been consumed
+    fn skip_comment(&mut self, start: usize) -> Result<(), LexError> {
+        while !self.at_end() {
             if self.ch == b'{' {
+                let nested_start = self.index;
                 self.next_char();
-                self.skip_comment(token)?;
+                self.skip_comment(nested_start)?;
             } else if self.ch == b'}' {
                 self.next_char();
                 return Ok(());
@@ -308,53 +919,201 @@ impl<'a> Lexer<'a> {
             }
         }
 
-        // force line number of error reporting
-        self.position = start_pos;
-        Err("comment not closed".to_string())
+        Err(self.error(
+            LexErrorKind::UnterminatedComment,
+            Span::new(start, self.index),
+        ))
+    }
+
+    // makes the "{! ... }" just skipped into a doc comment token, whose value is
+    // the text between the braces without surrounding whitespace
+    fn process_doc_comment(&mut self, token: &mut Token) {
+        let text = self.text(Span::new(self.start + 2, self.index - 1));
+        token.kind = TokenKind::DocComment;
+        token.value = TokenValue::Symbol(self.interner.intern(text.trim()));
     }
 
     fn skip_whitespace(&mut self) {
-        while self.has_next_char() {
-            if self.ch.is_ascii_whitespace() {
-                self.next_char();
+        let at_newline = |lexer: &Self| lexer.ch == b'\n' || lexer.ch == b'\r';
+
+        while !self.at_end() && self.ch.is_ascii_whitespace() {
+            let start = self.index;
+            if at_newline(self) {
+                let crlf = self.ch == b'\r' && self.peek_next() == b'\n';
+                self.skip_char(if crlf { 2 } else { 1 });
+                self.push_trivia(TriviaKind::Newline, start);
             } else {
-                break;
+                while !self.at_end() && self.ch.is_ascii_whitespace() && !at_newline(self) {
+                    self.next_char();
+                }
+                self.push_trivia(TriviaKind::Whitespace, start);
             }
         }
     }
+
+    // records the text from `start` up to `ch` as trivia, if trivia is retained
+    fn push_trivia(&mut self, kind: TriviaKind, start: usize) {
+        if self.options.retain_trivia {
+            let span = Span::new(start, self.index);
+            self.trivia.push(Trivia {
+                kind,
+                span,
+                text: self.text(span),
+            });
+        }
+    }
+
+    // removes and returns the trivia recorded since this was last called,
+    // which is the trivia before the token just read unless tokens have been
+    // peeked at. The lexer holds on to the trivia (and copies it into every
+    // checkpoint) until it is taken, so a caller retaining trivia should take
+    // it after every token.
+    pub fn take_trivia(&mut self) -> Vec<Trivia> {
+        std::mem::take(&mut self.trivia)
+    }
+
+    // the source text covered by `span`, which must not have been discarded
+    // yet, as is the case for the last token read
+    pub(crate) fn text(&self, span: Span) -> String {
+        String::from_utf8_lossy(self.source.slice(span.start, span.end)).into_owned()
+    }
+
+    // turns the lexer into an iterator over lossless tokens, each carrying the
+    // whitespace, newlines and comments around it
+    pub fn lossless(self) -> LosslessTokens<'a, S> {
+        LosslessTokens {
+            lexer: self.with_trivia(true),
+            pending: None,
+            error: None,
+        }
+    }
+}
+
+// an iterator over the lossless tokens of a source file. Concatenating the
+// tokens, which display as their trivia and text, reproduces the source.
+//
+// The trivia between two tokens up to and including the first newline is the
+// trailing trivia of the first token, the rest is the leading trivia of the
+// second token. TokenKind::Eof gets the trivia at the end of the file.
+pub struct LosslessTokens<'a, S = &'a [u8]> {
+    lexer: Lexer<'a, S>,
+
+    // the last token read, which is waiting for its trailing trivia
+    pending: Option<LosslessToken>,
+
+    // an error to report once the pending token has been yielded
+    error: Option<LexError>,
+}
+
+impl<S: Source> Iterator for LosslessTokens<'_, S> {
+    type Item = Result<LosslessToken, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(err) = self.error.take() {
+            return Some(Err(err));
+        }
+
+        loop {
+            let token = match self.lexer.next() {
+                Some(Ok(token)) => token,
+                Some(Err(err)) => match self.pending.take() {
+                    Some(previous) => {
+                        self.error = Some(err);
+                        return Some(Ok(previous));
+                    }
+                    None => return Some(Err(err)),
+                },
+                None => return self.pending.take().map(Ok),
+            };
+
+            let mut trivia = self.lexer.take_trivia();
+            let text = self.lexer.text(token.span);
+
+            let leading = match &mut self.pending {
+                Some(previous) => {
+                    let split = trivia
+                        .iter()
+                        .position(|trivia| trivia.kind == TriviaKind::Newline)
+                        .map_or(trivia.len(), |index| index + 1);
+                    let leading = trivia.split_off(split);
+                    previous.trailing = trivia;
+                    leading
+                }
+                None => trivia,
+            };
+
+            let current = LosslessToken {
+                token,
+                text,
+                leading,
+                trailing: Vec::new(),
+            };
+            if let Some(previous) = self.pending.replace(current) {
+                return Some(Ok(previous));
+            }
+        }
+    }
+}
+
+fn is_utf8_continuation(ch: u8) -> bool {
+    ch & 0xC0 == 0x80
+}
+
+impl<S: Source> Iterator for Lexer<'_, S> {
+    type Item = Result<Token, LexError>;
+
+    // yields every token up to and including TokenKind::Eof, or up to the first
+    // error (outside of recovery mode), after which the iterator is exhausted
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self.next_token();
+        self.done = matches!(
+            result,
+            Ok(Token {
+                kind: TokenKind::Eof,
+                ..
+            }) | Err(_)
+        );
+        Some(result)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::intern::Symbol;
+    use crate::token::RESERVED_WORDS;
 
     #[test]
     fn test_basic_get_token() {
         let input = "relax".as_bytes();
         let mut lexer = Lexer::new(input);
-        let mut token: Token = Token::Eof;
+        let mut token = Token::default();
         lexer.get_token(&mut token).unwrap();
-        assert_eq!(token, Token::Relax);
+        assert_eq!(token.kind, TokenKind::Relax);
     }
 
     #[test]
     fn test_multiple_get_token_calls() {
         let input = "if then and while < >".as_bytes();
         let mut lexer = Lexer::new(input);
-        let mut token: Token = Token::Eof;
+        let mut token = Token::default();
 
         let expected_tokens = vec![
-            Token::If,
-            Token::Then,
-            Token::And,
-            Token::While,
-            Token::LessThan,
-            Token::GreaterThan,
+            TokenKind::If,
+            TokenKind::Then,
+            TokenKind::And,
+            TokenKind::While,
+            TokenKind::LessThan,
+            TokenKind::GreaterThan,
         ];
 
         for expected_token in expected_tokens {
             lexer.get_token(&mut token).unwrap();
-            assert_eq!(token, expected_token);
+            assert_eq!(token.kind, expected_token);
         }
     }
 
@@ -362,48 +1121,51 @@ mod tests {
     fn test_consuming_all_operators() {
         let input = "= >= > <= < <> - + / * ] ) , . := [ ( ;".as_bytes();
         let mut lexer = Lexer::new(input);
-        let mut token: Token = Token::Eof;
+        let mut token = Token::default();
 
         let expected_tokens = vec![
-            Token::Equal,
-            Token::GreaterEqual,
-            Token::GreaterThan,
-            Token::LessEqual,
-            Token::LessThan,
-            Token::NotEqual,
-            Token::Minus,
-            Token::Plus,
-            Token::Divide,
-            Token::Multiply,
-            Token::CloseBracket,
-            Token::CloseParenthesis,
-            Token::Comma,
-            Token::Concatenate,
-            Token::Gets,
-            Token::OpenBracket,
-            Token::OpenParenthesis,
-            Token::Semicolon,
+            TokenKind::Equal,
+            TokenKind::GreaterEqual,
+            TokenKind::GreaterThan,
+            TokenKind::LessEqual,
+            TokenKind::LessThan,
+            TokenKind::NotEqual,
+            TokenKind::Minus,
+            TokenKind::Plus,
+            TokenKind::Divide,
+            TokenKind::Multiply,
+            TokenKind::CloseBracket,
+            TokenKind::CloseParenthesis,
+            TokenKind::Comma,
+            TokenKind::Concatenate,
+            TokenKind::Gets,
+            TokenKind::OpenBracket,
+            TokenKind::OpenParenthesis,
+            TokenKind::Semicolon,
         ];
         for expected_token in expected_tokens {
             lexer.get_token(&mut token).unwrap();
-            assert_eq!(token, expected_token);
+            assert_eq!(token.kind, expected_token);
         }
     }
 
     #[test]
     fn test_process_string_then_number_then_string() {
         let input = "\"hello\" 42 \"World\"".as_bytes();
-        let mut lexer = Lexer::new(input);
-        let mut token: Token = Token::Eof;
+        let mut interner = Interner::new();
+        let hello = interner.intern("hello");
+        let world = interner.intern("World");
+        let mut lexer = Lexer::new(input).with_interner(interner);
+        let mut token = Token::default();
 
         let expected_tokens = vec![
-            Token::StringLiteral(String::from("hello")),
-            Token::Number(42),
-            Token::StringLiteral(String::from("World")),
+            (TokenKind::StringLiteral, TokenValue::Symbol(hello)),
+            (TokenKind::Number, TokenValue::Number(42)),
+            (TokenKind::StringLiteral, TokenValue::Symbol(world)),
         ];
         for expected_token in expected_tokens {
             lexer.get_token(&mut token).unwrap();
-            assert_eq!(token, expected_token);
+            assert_eq!((token.kind, token.value), expected_token);
         }
     }
 
@@ -411,42 +1173,677 @@ mod tests {
     fn test_process_word() {
         let input = "if boolean then".as_bytes();
         let mut lexer = Lexer::new(input);
-        let mut token: Token = Token::Eof;
+        let mut token = Token::default();
 
-        let expected_tokens = vec![Token::If, Token::Boolean, Token::Then];
+        let expected_tokens = vec![TokenKind::If, TokenKind::Boolean, TokenKind::Then];
         for expected_token in expected_tokens {
             lexer.get_token(&mut token).unwrap();
-            assert_eq!(token, expected_token);
+            assert_eq!(token.kind, expected_token);
         }
     }
 
     #[test]
     fn test_process_word_with_underscore() {
         let input = "while variable_name do".as_bytes();
-        let mut lexer = Lexer::new(input);
-        let mut token: Token = Token::Eof;
+        let mut interner = Interner::new();
+        let variable_name = interner.intern("variable_name");
+        let mut lexer = Lexer::new(input).with_interner(interner);
+        let mut token = Token::default();
 
         let expected_tokens = vec![
-            Token::While,
-            Token::Id(String::from("variable_name")),
-            Token::Do,
+            (TokenKind::While, TokenValue::None),
+            (TokenKind::Id, TokenValue::Symbol(variable_name)),
+            (TokenKind::Do, TokenValue::None),
         ];
         for expected_token in expected_tokens {
             lexer.get_token(&mut token).unwrap();
-            assert_eq!(token, expected_token);
+            assert_eq!((token.kind, token.value), expected_token);
         }
     }
 
+    #[test]
+    fn test_process_word_every_keyword() {
+        let input: Vec<&str> = RESERVED_WORDS.iter().map(|(text, _)| *text).collect();
+        let input = input.join(" ");
+        let lexer = Lexer::new(input.as_bytes());
+
+        let kinds: Vec<TokenKind> = lexer.map(|token| token.unwrap().kind).collect();
+        let mut expected: Vec<TokenKind> = RESERVED_WORDS.iter().map(|(_, kind)| *kind).collect();
+        expected.push(TokenKind::Eof);
+        assert_eq!(kinds, expected);
+    }
+
+    #[test]
+    fn test_identifier_length_limit() {
+        let options = LexerOptions {
+            max_id_length: 4,
+            ..LexerOptions::default()
+        };
+
+        let kinds: Vec<TokenKind> = Lexer::new_with_options("abcd;".as_bytes(), options.clone())
+            .map(|token| token.unwrap().kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![TokenKind::Id, TokenKind::Semicolon, TokenKind::Eof]
+        );
+
+        let mut lexer = Lexer::new_with_options("abcde;".as_bytes(), options);
+        let err = lexer.next_token().unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::IdentifierTooLong);
+        assert_eq!(err.span, Span::new(0, 5));
+
+        let long_name = "x".repeat(MAX_ID_LENGTH);
+        assert!(Lexer::new(long_name.as_bytes()).all(|token| token.is_ok()));
+    }
+
+    #[test]
+    fn test_options_tab_width() {
+        let options = LexerOptions {
+            tab_width: 8,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::new_with_options("\tif\t#".as_bytes(), options);
+
+        lexer.next_token().unwrap();
+        let err = lexer.next_token().unwrap_err();
+        assert_eq!(err.position, SourcePosition { line: 1, col: 17 });
+    }
+
+    #[test]
+    fn test_crlf_and_lone_cr_line_endings() {
+        let input = "if\r\n\tthen\r#".as_bytes();
+        let mut lexer = Lexer::new(input);
+
+        lexer.next_token().unwrap();
+        lexer.next_token().unwrap();
+        let err = lexer.next_token().unwrap_err();
+        assert_eq!(err.position, SourcePosition { line: 3, col: 1 });
+        assert_eq!(
+            lexer.source_map().position(5),
+            SourcePosition { line: 2, col: 5 }
+        );
+
+        let kinds: Vec<TriviaKind> = Lexer::new("if\r\n\tthen\r".as_bytes())
+            .lossless()
+            .flat_map(|token| token.unwrap().trailing)
+            .map(|trivia| trivia.kind)
+            .collect();
+        assert_eq!(kinds, vec![TriviaKind::Newline, TriviaKind::Newline]);
+    }
+
+    #[test]
+    fn test_take_trivia() {
+        let mut lexer = Lexer::new("put { x } 1\n".as_bytes()).with_trivia(true);
+        let mut texts = Vec::new();
+        while lexer.next_token().unwrap().kind != TokenKind::Eof {
+            let trivia = lexer.take_trivia();
+            texts.push(
+                trivia
+                    .iter()
+                    .map(|trivia| trivia.text.clone())
+                    .collect::<Vec<_>>(),
+            );
+        }
+        assert_eq!(texts, [vec![], vec![" ", "{ x }", " "]]);
+        assert_eq!(lexer.take_trivia()[0].kind, TriviaKind::Newline);
+        assert!(lexer.take_trivia().is_empty());
+    }
+
     #[test]
     fn test_process_comment() {
         let input = "if { this is a comment } then".as_bytes();
         let mut lexer = Lexer::new(input);
-        let mut token: Token = Token::Eof;
+        let mut token = Token::default();
 
-        let expected_tokens = vec![Token::If, Token::Then];
+        let expected_tokens = vec![TokenKind::If, TokenKind::Then];
         for expected_token in expected_tokens {
             lexer.get_token(&mut token).unwrap();
-            assert_eq!(token, expected_token);
+            assert_eq!(token.kind, expected_token);
+        }
+    }
+
+    #[test]
+    fn test_line_comments() {
+        let input = "x // y { z\r\n/ x//".as_bytes();
+        let kinds: Vec<TokenKind> = Lexer::new(input).map(|token| token.unwrap().kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Id,
+                TokenKind::Divide,
+                TokenKind::Id,
+                TokenKind::Eof
+            ]
+        );
+
+        let lossless: String = Lexer::new(input)
+            .lossless()
+            .map(|token| token.unwrap().to_string())
+            .collect();
+        assert_eq!(lossless.as_bytes(), input);
+    }
+
+    #[test]
+    fn test_many_comments_in_a_row() {
+        let input = format!("{}{}x", "// line\n".repeat(100_000), "{}".repeat(100_000));
+        let mut lexer = Lexer::new(input.as_bytes());
+        assert_eq!(lexer.next_token().unwrap().kind, TokenKind::Id);
+        assert_eq!(lexer.next_token().unwrap().kind, TokenKind::Eof);
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let input = "{! Adds { nested } numbers. }\nfunction {!} { ! }".as_bytes();
+        let mut lexer = Lexer::new(input);
+
+        let doc = lexer.next_token().unwrap();
+        assert_eq!(doc.kind, TokenKind::DocComment);
+        assert_eq!(doc.span, Span::new(0, 29));
+        let text = lexer.interner().resolve(doc.symbol().unwrap());
+        assert_eq!(text, "Adds { nested } numbers.");
+
+        assert_eq!(lexer.next_token().unwrap().kind, TokenKind::Function);
+        let empty = lexer.next_token().unwrap();
+        assert_eq!(empty.kind, TokenKind::DocComment);
+        assert_eq!(lexer.interner().resolve(empty.symbol().unwrap()), "");
+        assert_eq!(lexer.next_token().unwrap().kind, TokenKind::Eof);
+
+        let err = Lexer::new("{! open".as_bytes()).next_token().unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::UnterminatedComment);
+    }
+
+    #[test]
+    fn test_iterator_stops_after_eof() {
+        let input = "get x;".as_bytes();
+        let tokens: Vec<TokenKind> = Lexer::new(input)
+            .map(|result| result.unwrap().kind)
+            .collect();
+
+        let expected_tokens = vec![
+            TokenKind::Get,
+            TokenKind::Id,
+            TokenKind::Semicolon,
+            TokenKind::Eof,
+        ];
+        assert_eq!(tokens, expected_tokens);
+    }
+
+    #[test]
+    fn test_iterator_stops_after_error() {
+        let input = "put : relax".as_bytes();
+        let results: Vec<_> = Lexer::new(input).collect();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap().kind, TokenKind::Put);
+        assert!(results[1].is_err());
+    }
+
+    #[test]
+    fn test_error_kind_and_display() {
+        let input = "relax\n  \"open".as_bytes();
+        let mut lexer = Lexer::new(input).with_file_name("test.svl");
+
+        assert_eq!(lexer.next().unwrap().unwrap().kind, TokenKind::Relax);
+
+        let err = lexer.next().unwrap().unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::UnterminatedString);
+        assert_eq!(err.file_name.as_deref(), Some("test.svl"));
+        assert_eq!(err.span, Span::new(8, 13));
+        assert_eq!(err.to_string(), "test.svl:2:3: string not closed");
+    }
+
+    #[test]
+    fn test_token_spans() {
+        let input = "x := \"abc\" { note }\n  42".as_bytes();
+        let spans: Vec<Span> = Lexer::new(input)
+            .map(|result| result.unwrap().span)
+            .collect();
+
+        let expected_spans = vec![
+            Span::new(0, 1),
+            Span::new(2, 4),
+            Span::new(5, 10),
+            Span::new(22, 24),
+            Span::new(24, 24),
+        ];
+        assert_eq!(spans, expected_spans);
+    }
+
+    #[test]
+    fn test_recovery_reports_all_errors() {
+        let input = "x := 99999999999 # \"a\\qb\" ; y".as_bytes();
+        let mut lexer = Lexer::new(input).with_recovery(true);
+        let tokens: Vec<TokenKind> = lexer.by_ref().map(|result| result.unwrap().kind).collect();
+
+        let expected_tokens = vec![
+            TokenKind::Id,
+            TokenKind::Gets,
+            TokenKind::Error,
+            TokenKind::Error,
+            TokenKind::Error,
+            TokenKind::Semicolon,
+            TokenKind::Id,
+            TokenKind::Eof,
+        ];
+        assert_eq!(tokens, expected_tokens);
+
+        let kinds: Vec<LexErrorKind> = lexer
+            .take_errors()
+            .into_iter()
+            .map(|err| err.kind)
+            .collect();
+        let expected_kinds = vec![
+            LexErrorKind::NumberTooLarge,
+            LexErrorKind::IllegalCharacter('#'),
+            LexErrorKind::IllegalEscape('q'),
+        ];
+        assert_eq!(kinds, expected_kinds);
+    }
+
+    #[test]
+    fn test_process_string_escapes() {
+        let source = r#"put "a\tb\n\"q\"\\\r\0\x41\u{e9}\u{1F600}""#;
+        let mut lexer = Lexer::new(source.as_bytes());
+        lexer.next();
+
+        // the decoded value is in the token, the raw lexeme in the source
+        let token = lexer.next().unwrap().unwrap();
+        assert_eq!(token.kind, TokenKind::StringLiteral);
+        assert_eq!(
+            lexer.interner().resolve(token.symbol().unwrap()),
+            "a\tb\n\"q\"\\\r\0A\u{e9}\u{1F600}"
+        );
+        assert_eq!(token.span.text(source), &source[4..]);
+    }
+
+    #[test]
+    fn test_invalid_escape_spans() {
+        let cases = vec![
+            (
+                r#""ab\qc""#,
+                LexErrorKind::IllegalEscape('q'),
+                Span::new(3, 5),
+            ),
+            (
+                r#""a\éb""#,
+                LexErrorKind::IllegalEscape('é'),
+                Span::new(2, 5),
+            ),
+            (
+                r#""\😀""#,
+                LexErrorKind::IllegalEscape('😀'),
+                Span::new(1, 6),
+            ),
+            (r#""\x4""#, LexErrorKind::InvalidHexEscape, Span::new(1, 4)),
+            (r#""\xFF""#, LexErrorKind::InvalidHexEscape, Span::new(1, 5)),
+            (
+                r#""\u{}""#,
+                LexErrorKind::InvalidUnicodeEscape,
+                Span::new(1, 5),
+            ),
+            (
+                r#""\u{D800}""#,
+                LexErrorKind::InvalidUnicodeEscape,
+                Span::new(1, 9),
+            ),
+            (
+                r#""\u41""#,
+                LexErrorKind::InvalidUnicodeEscape,
+                Span::new(1, 3),
+            ),
+        ];
+
+        for (input, expected_kind, expected_span) in cases {
+            let err = Lexer::new(input.as_bytes()).next_token().unwrap_err();
+            assert_eq!(err.kind, expected_kind);
+            assert_eq!(err.span, expected_span);
+        }
+    }
+
+    #[test]
+    fn test_utf8_strings_and_comments() {
+        let input = "{ commentaire: déjà vu } put \"Grüße, 世界\"".as_bytes();
+        let mut interner = Interner::new();
+        let greeting = interner.intern("Grüße, 世界");
+        let tokens: Vec<Token> = Lexer::new(input)
+            .with_interner(interner)
+            .map(|result| result.unwrap())
+            .collect();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[0].kind, TokenKind::Put);
+        assert_eq!(tokens[1].kind, TokenKind::StringLiteral);
+        assert_eq!(tokens[1].symbol(), Some(greeting));
+    }
+
+    #[test]
+    fn test_invalid_utf8_positions() {
+        // invalid UTF-8 in a comment is skipped, each byte counting as a column
+        let input = b"x { \xC0 } #";
+        let err = Lexer::new(input).nth(1).unwrap().unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::IllegalCharacter('#'));
+        assert_eq!(err.position, SourcePosition { line: 1, col: 9 });
+
+        // a truncated character followed by ASCII
+        let input = b"{ \xF0# } #";
+        let mut lexer = Lexer::new(input);
+        let err = lexer.next_token().unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::IllegalCharacter('#'));
+        assert_eq!(err.position, SourcePosition { line: 1, col: 8 });
+        assert_eq!(lexer.source_map(), &SourceMap::from_source(input));
+    }
+
+    #[test]
+    fn test_unicode_identifiers() {
+        let input = "größe := ñandú_2".as_bytes();
+
+        let err = Lexer::new(input).nth(1).unwrap().unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::IllegalCharacter('ö'));
+
+        let mut interner = Interner::new();
+        let grosse = interner.intern("größe");
+        let nandu = interner.intern("ñandú_2");
+        let symbols: Vec<Option<Symbol>> = Lexer::new(input)
+            .with_interner(interner)
+            .with_unicode_identifiers(true)
+            .map(|result| result.unwrap().symbol())
+            .collect();
+
+        assert_eq!(symbols, vec![Some(grosse), None, Some(nandu), None]);
+    }
+
+    #[test]
+    fn test_columns_count_characters() {
+        let input = "put \"ünïcödé\" @".as_bytes();
+        let err = Lexer::new(input).nth(2).unwrap().unwrap_err();
+
+        assert_eq!(err.kind, LexErrorKind::IllegalCharacter('@'));
+        assert_eq!(err.span, Span::new(18, 19));
+        assert_eq!(err.position.col, 15);
+    }
+
+    #[test]
+    fn test_empty_source() {
+        let tokens: Vec<TokenKind> = Lexer::new(&[]).map(|result| result.unwrap().kind).collect();
+        assert_eq!(tokens, vec![TokenKind::Eof]);
+    }
+
+    #[test]
+    fn test_observer_sees_tokens_and_errors() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        struct Recorder(Rc<RefCell<Vec<String>>>);
+
+        impl LexerObserver for Recorder {
+            fn on_token(&mut self, token: &Token, position: SourcePosition) {
+                self.0
+                    .borrow_mut()
+                    .push(format!("{}:{} {}", position.line, position.col, token.kind));
+            }
+
+            fn on_error(&mut self, err: &LexError) {
+                self.0.borrow_mut().push(err.to_string());
+            }
+        }
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let input = "relax\n  ?".as_bytes();
+        let lexer = Lexer::new(input)
+            .with_recovery(true)
+            .with_observer(Box::new(Recorder(Rc::clone(&events))));
+        assert_eq!(lexer.count(), 3);
+
+        let expected_events = vec![
+            "1:1 Relax",
+            "2:3: illegal character '?' (ASCII #63)",
+            "2:3 Error",
+            "2:4 Eof",
+        ];
+        assert_eq!(*events.borrow(), expected_events);
+    }
+
+    #[test]
+    fn test_process_number_radixes_and_separators() {
+        let input = "1_000 0x7fFF 0b1010 0o17 007 0x_1".as_bytes();
+        let numbers: Vec<Option<u64>> = Lexer::new(input)
+            .map(|result| result.unwrap().number())
+            .collect();
+
+        let expected_numbers = vec![
+            Some(1000),
+            Some(0x7fff),
+            Some(0b1010),
+            Some(0o17),
+            Some(7),
+            Some(1),
+            None,
+        ];
+        assert_eq!(numbers, expected_numbers);
+    }
+
+    #[test]
+    fn test_process_number_integer_width() {
+        let input = "2147483648 2147483649 9223372036854775808".as_bytes();
+
+        let results: Vec<_> = Lexer::new(input).with_recovery(true).collect();
+        assert_eq!(results[0].as_ref().unwrap().kind, TokenKind::Error);
+        assert_eq!(results[1].as_ref().unwrap().kind, TokenKind::Error);
+        assert_eq!(results[2].as_ref().unwrap().kind, TokenKind::Error);
+
+        let numbers: Vec<Option<u64>> = Lexer::new(input)
+            .with_integer_width(IntegerWidth::I64)
+            .with_recovery(true)
+            .map(|result| result.unwrap().number())
+            .collect();
+        assert_eq!(numbers[..3], [Some(2147483648), Some(2147483649), None]);
+        let mut lexer = Lexer::new(b"-9223372036854775808").with_integer_width(IntegerWidth::I64);
+        lexer.next_token().unwrap();
+        assert_eq!(
+            lexer.next_token().unwrap().number(),
+            Some(9223372036854775808)
+        );
+
+        // one past the largest integer is only allowed after a '-', so that
+        // the smallest one can be written
+        let input = "-2147483648 - { min } 2147483648 (2147483648".as_bytes();
+        let kinds: Vec<TokenKind> = Lexer::new(input)
+            .with_recovery(true)
+            .map(|result| result.unwrap().kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::Minus,
+                TokenKind::Number,
+                TokenKind::Minus,
+                TokenKind::Number,
+                TokenKind::OpenParenthesis,
+                TokenKind::Error,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed_number_spans() {
+        let cases = vec![
+            (
+                "x 0b1021",
+                LexErrorKind::InvalidDigit {
+                    digit: '2',
+                    radix: 2,
+                },
+                Span::new(6, 7),
+            ),
+            (
+                "x 0o78",
+                LexErrorKind::InvalidDigit {
+                    digit: '8',
+                    radix: 8,
+                },
+                Span::new(5, 6),
+            ),
+            ("x 0x_", LexErrorKind::MissingDigits, Span::new(2, 5)),
+            (
+                "x 0x1_0000_0000_0000_0000",
+                LexErrorKind::NumberTooLarge,
+                Span::new(2, 25),
+            ),
+        ];
+
+        for (input, expected_kind, expected_span) in cases {
+            let err = Lexer::new(input.as_bytes()).nth(1).unwrap().unwrap_err();
+            assert_eq!(err.kind, expected_kind);
+            assert_eq!(err.span, expected_span);
         }
     }
+
+    #[test]
+    fn test_lossless_round_trip() {
+        let source = "  { header {nested} }\r\nx := 1; { note }\n\n\tput x\n{ trailer }\n";
+        let tokens: Vec<LosslessToken> = Lexer::new(source.as_bytes())
+            .lossless()
+            .map(|result| result.unwrap())
+            .collect();
+
+        let round_trip: String = tokens.iter().map(|token| token.to_string()).collect();
+        assert_eq!(round_trip, source);
+
+        let texts = |trivia: &[Trivia]| -> Vec<String> {
+            trivia.iter().map(|trivia| trivia.text.clone()).collect()
+        };
+        assert_eq!(
+            texts(&tokens[0].leading),
+            ["  ", "{ header {nested} }", "\r\n"]
+        );
+        assert_eq!(texts(&tokens[3].trailing), [" ", "{ note }", "\n"]);
+        assert_eq!(texts(&tokens[4].leading), ["\n", "\t"]);
+        assert_eq!(tokens[5].text, "x");
+        assert_eq!(texts(&tokens[6].leading), ["{ trailer }", "\n"]);
+        assert_eq!(tokens[6].token.kind, TokenKind::Eof);
+    }
+
+    #[test]
+    fn test_lossless_round_trip_with_errors() {
+        let source = "x := # 99999999999 \"a\\q\" y { open";
+        let round_trip: String = Lexer::new(source.as_bytes())
+            .with_recovery(true)
+            .lossless()
+            .map(|result| result.unwrap().to_string())
+            .collect();
+        assert_eq!(round_trip, source);
+    }
+
+    #[test]
+    fn test_peek_and_peek_nth() {
+        let input = "x[1] := 2".as_bytes();
+        let mut interner = Interner::new();
+        let x = interner.intern("x");
+        let mut lexer = Lexer::new(input).with_interner(interner);
+
+        assert_eq!(lexer.peek().unwrap().symbol(), Some(x));
+        assert_eq!(lexer.peek_nth(1).unwrap().kind, TokenKind::OpenBracket);
+        assert_eq!(lexer.peek_nth(4).unwrap().kind, TokenKind::Gets);
+        assert_eq!(lexer.peek_nth(10).unwrap().kind, TokenKind::Eof);
+
+        let tokens: Vec<TokenKind> = lexer.map(|result| result.unwrap().kind).collect();
+        let expected_tokens = vec![
+            TokenKind::Id,
+            TokenKind::OpenBracket,
+            TokenKind::Number,
+            TokenKind::CloseBracket,
+            TokenKind::Gets,
+            TokenKind::Number,
+            TokenKind::Eof,
+        ];
+        assert_eq!(tokens, expected_tokens);
+    }
+
+    #[test]
+    fn test_checkpoint_and_restore() {
+        let input = "if a\n# then\nb".as_bytes();
+        let mut lexer = Lexer::new(input).with_recovery(true);
+
+        assert_eq!(lexer.next_token().unwrap().kind, TokenKind::If);
+        lexer.peek().unwrap();
+        let checkpoint = lexer.checkpoint();
+
+        let first_pass: Vec<TokenKind> =
+            lexer.by_ref().map(|result| result.unwrap().kind).collect();
+        assert_eq!(lexer.errors().len(), 1);
+        assert_eq!(lexer.source_map().line_count(), 3);
+
+        lexer.restore(&checkpoint);
+        assert!(lexer.errors().is_empty());
+        assert_eq!(lexer.source_map().line_count(), 1);
+
+        let second_pass: Vec<TokenKind> =
+            lexer.by_ref().map(|result| result.unwrap().kind).collect();
+        assert_eq!(first_pass, second_pass);
+        assert_eq!(lexer.errors()[0].position.line, 2);
+    }
+
+    #[test]
+    fn test_from_reader_matches_slice_lexer() {
+        let source =
+            "source demo\nbegin\n  integer x;\n  x := 0x2A; { über }\n  put \"x = \" . x\nend\n";
+        let reader = std::io::BufReader::with_capacity(4, source.as_bytes());
+
+        let from_slice: Vec<_> = Lexer::new(source.as_bytes()).collect();
+        let mut lexer = Lexer::from_reader(reader);
+        let from_reader: Vec<_> = lexer.by_ref().collect();
+        assert_eq!(from_reader, from_slice);
+        assert_eq!(
+            lexer.source_map(),
+            &SourceMap::from_source(source.as_bytes())
+        );
+    }
+
+    #[test]
+    fn test_from_reader_reports_io_errors() {
+        struct FailingReader;
+
+        impl std::io::Read for FailingReader {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("disk on fire"))
+            }
+        }
+
+        let mut lexer = Lexer::from_reader(std::io::BufReader::new(FailingReader));
+        let err = lexer.next_token().unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::Io(String::from("disk on fire")));
+
+        // in recovery mode the error becomes a token, and the lexer stops
+        let kinds: Vec<TokenKind> = Lexer::from_reader(std::io::BufReader::new(FailingReader))
+            .with_recovery(true)
+            .map(|result| result.unwrap().kind)
+            .collect();
+        assert_eq!(kinds, [TokenKind::Error, TokenKind::Eof]);
+    }
+
+    #[test]
+    fn test_from_reader_keeps_peeked_tokens() {
+        let source = "relax; ".repeat(100);
+        let reader = std::io::BufReader::with_capacity(4, source.as_bytes());
+
+        let mut lexer = Lexer::from_reader(reader);
+        assert_eq!(lexer.peek_nth(60).unwrap().kind, TokenKind::Relax);
+        for _ in 0..100 {
+            let token = lexer.next_token().unwrap();
+            assert_eq!(lexer.text(token.span), "relax");
+            let token = lexer.next_token().unwrap();
+            assert_eq!(lexer.text(token.span), ";");
+        }
+
+        let reader = std::io::BufReader::with_capacity(4, source.as_bytes());
+        let mut lexer = Lexer::from_reader(reader);
+        lexer.peek_nth(60).unwrap();
+        let texts: Vec<String> = lexer.lossless().map(|token| token.unwrap().text).collect();
+        assert_eq!(texts.len(), 201);
+        assert!(texts[..200]
+            .iter()
+            .all(|text| text == "relax" || text == ";"));
+    }
 }
@@ -1,3 +1,12 @@
+pub mod ast;
+pub mod cst;
 pub mod error;
+pub mod intern;
 pub mod lexer;
+pub mod parser;
+pub mod resolve;
+pub mod source;
+pub mod span;
 pub mod token;
+pub mod typecheck;
+pub mod typed;
@@ -1,27 +1,72 @@
-use std::fs;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::{env, process};
 
-use svlang::lexer::Lexer;
-use svlang::token::Token;
+use svlang::error::{LexError, SourcePosition};
+use svlang::lexer::{Lexer, LexerObserver};
+use svlang::token::{Token, TokenValue};
+
+// prints every token and error the lexer produces to stderr
+struct LexerTracer;
+
+impl LexerObserver for LexerTracer {
+    fn on_token(&mut self, token: &Token, position: SourcePosition) {
+        eprintln!(
+            "[lexer] {}:{} {:?} {}..{}",
+            position.line, position.col, token.kind, token.span.start, token.span.end
+        );
+    }
+
+    fn on_error(&mut self, err: &LexError) {
+        eprintln!("[lexer] {}", err);
+    }
+}
 
 fn main() {
-    let source = fs::read_to_string("samples/hello.jaxon").expect("Could not read the file.");
+    let mut file_name = None;
+    let mut trace_lexer = false;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--trace-lexer" => trace_lexer = true,
+            _ => file_name = Some(arg),
+        }
+    }
 
-    let mut lexer = Lexer::new(source.as_bytes());
-    let mut token: Token = Token::Eof;
+    // read from stdin when no file is given, so the compiler can be used as a
+    // filter
+    let file_name = file_name.unwrap_or_else(|| String::from("-"));
+    let reader: Box<dyn BufRead> = if file_name == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(
+            File::open(&file_name).expect("Could not open the file."),
+        ))
+    };
 
-    lexer.get_token(&mut token).unwrap();
-    println!("{:?}", token);
-    assert!(token == Token::Function);
+    let mut lexer = Lexer::from_reader(reader)
+        .with_file_name(&file_name)
+        .with_recovery(true);
+    if trace_lexer {
+        lexer = lexer.with_observer(Box::new(LexerTracer));
+    }
 
-    lexer.get_token(&mut token).unwrap();
-    println!("{:?}", token);
-    assert!(token == Token::Id(String::from("name")));
+    while let Some(result) = lexer.next() {
+        let token = result.unwrap();
+        match token.value {
+            TokenValue::None => println!("{}", token.kind),
+            TokenValue::Symbol(symbol) => {
+                println!("{}({:?})", token.kind, lexer.interner().resolve(symbol))
+            }
+            TokenValue::Number(number) => println!("{}({})", token.kind, number),
+        }
+    }
 
-    lexer.get_token(&mut token).unwrap();
-    println!("{:?}", token);
-    assert!(token == Token::Number(1235));
+    let errors = lexer.take_errors();
+    for err in &errors {
+        eprintln!("{}", err);
+    }
 
-    lexer.get_token(&mut token).unwrap();
-    println!("{:?}", token);
-    assert!(token == Token::StringLiteral(String::from("string")))
+    if !errors.is_empty() {
+        process::exit(1);
+    }
 }
@@ -0,0 +1,1104 @@
+mod expr;
+
+pub(crate) use expr::binary_op;
+
+use crate::ast::{
+    AssignValue, BaseType, Body, Expr, FunctionDecl, Ident, IfBranch, Param, Program, Stmt, Type,
+    VarDecl,
+};
+use crate::cst::{GreenBuilder, LeafKind, Marker, NodeKind, SyntaxNode};
+use crate::error::{ParseError, ParseErrorKind};
+use crate::intern::{Interner, Symbol};
+use crate::lexer::Lexer;
+use crate::source::Source;
+use crate::span::{SourceMap, Span};
+use crate::token::{Token, TokenKind};
+
+// a recursive-descent parser turning the tokens of a lexer into a Program, see
+// the grammar rules on the nodes in ast.rs
+pub struct Parser<'a, S = &'a [u8]> {
+    lexer: Lexer<'a, S>,
+
+    // the token being looked at
+    token: Token,
+
+    // the end of the last token consumed, which is where the node being parsed
+    // ends
+    previous_end: usize,
+
+    // the text of the doc comment right before `token`, if any
+    doc: Option<Symbol>,
+
+    // whether syntax errors are recorded and parsing carries on after them
+    recover: bool,
+
+    // the errors recorded so far in recovery mode
+    errors: Vec<ParseError>,
+
+    // builds the syntax tree, if it was asked for
+    builder: Option<GreenBuilder>,
+
+    // the syntax tree of the program, once it has been parsed
+    syntax: Option<SyntaxNode>,
+}
+
+impl<'a, S: Source> Parser<'a, S> {
+    pub fn new(lexer: Lexer<'a, S>) -> Self {
+        Parser {
+            lexer,
+            token: Token::default(),
+            previous_end: 0,
+            doc: None,
+            recover: false,
+            errors: Vec::new(),
+            builder: None,
+            syntax: None,
+        }
+    }
+
+    // makes the parser build a lossless syntax tree alongside the AST, which
+    // keeps every token, comment and whitespace, see `syntax_tree`
+    pub fn with_syntax_tree(mut self) -> Self {
+        self.lexer = self.lexer.with_trivia(true);
+        self.builder = Some(GreenBuilder::new());
+        self
+    }
+
+    // turns on recovery mode for both the parser and its lexer. A syntax error
+    // is then recorded (see `errors`) and replaced by an error node in the AST:
+    // a missing token is taken to be there, and otherwise the parser skips
+    // ahead to a ';', "end", "begin", "function" or statement keyword. Only
+    // errors in the program header still stop the parser.
+    pub fn with_recovery(mut self, recover: bool) -> Self {
+        self.lexer = self.lexer.with_recovery(recover);
+        self.recover = recover;
+        self
+    }
+
+    // the lexical and syntax errors recorded in recovery mode, in the order
+    // they appear in the source once the program has been parsed
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    // removes and returns the errors recorded so far in recovery mode
+    pub fn take_errors(&mut self) -> Vec<ParseError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    // the interner holding the symbols of the identifiers parsed so far
+    pub fn interner(&self) -> &Interner {
+        self.lexer.interner()
+    }
+
+    // the line starts of the source read so far, to find the position of the
+    // nodes of the AST
+    pub fn source_map(&self) -> &SourceMap {
+        self.lexer.source_map()
+    }
+
+    // the file name reported in errors, if set
+    pub fn file_name(&self) -> Option<&str> {
+        self.lexer.file_name()
+    }
+
+    // hands the interner over to the next stage of the compiler
+    pub fn into_interner(self) -> Interner {
+        self.lexer.into_interner()
+    }
+
+    // the syntax tree of the program parsed, if it was asked for with
+    // `with_syntax_tree` and the parser did not stop at an error
+    pub fn syntax_tree(&self) -> Option<SyntaxNode> {
+        self.syntax.clone()
+    }
+
+    // parses a whole source file, stopping at the first error unless in
+    // recovery mode
+    pub fn parse_program(&mut self) -> Result<Program, ParseError> {
+        let result = self.parse_source();
+
+        let lex_errors = self.lexer.take_errors();
+        self.errors
+            .extend(lex_errors.into_iter().map(ParseError::from));
+        self.errors.sort_by_key(|err| err.span.start);
+        result
+    }
+
+    // "source" id {funcdef} body
+    fn parse_source(&mut self) -> Result<Program, ParseError> {
+        self.start_node(NodeKind::Program);
+        self.read_token()?;
+        let start = self.token.span.start;
+
+        self.expect(TokenKind::Source)?;
+        let name = self.expect_ident()?;
+        let mut functions = Vec::new();
+        while self.at(TokenKind::Function) {
+            let marker = self.marker();
+            match self.parse_function() {
+                Ok(function) => functions.push(function),
+                Err(err) if self.recover => {
+                    // a function without a proper header is left out, but its
+                    // body is still checked for errors
+                    self.report(err);
+                    self.finish_nodes_to(marker);
+                    self.skip_until(|kind| matches!(kind, TokenKind::Begin | TokenKind::Function))?;
+                    if self.at(TokenKind::Begin) {
+                        self.parse_body()?;
+                    }
+                    self.start_node_at(marker, NodeKind::Error);
+                    self.finish_node();
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        let body = self.parse_body()?;
+        let span = self.span_from(start);
+        self.expect(TokenKind::Eof)?;
+        if !self.at(TokenKind::Eof) {
+            // the tokens after the program, in recovery mode
+            self.start_node(NodeKind::Error);
+            self.skip_until(|_| false)?;
+            self.finish_node();
+        }
+
+        if let Some(mut builder) = self.builder.take() {
+            builder.token(TokenKind::Eof, String::new());
+            builder.finish_node();
+            self.syntax = Some(SyntaxNode::new_root(builder.finish()));
+        }
+
+        Ok(Program {
+            name,
+            functions,
+            body,
+            span,
+        })
+    }
+
+    fn parse_function(&mut self) -> Result<FunctionDecl, ParseError> {
+        let doc = self.doc.take();
+        let start = self.token.span.start;
+
+        self.start_node(NodeKind::Function);
+        self.expect(TokenKind::Function)?;
+        let name = self.expect_ident()?;
+        self.start_node(NodeKind::ParamList);
+        self.expect(TokenKind::OpenParenthesis)?;
+        let mut params = Vec::new();
+        if !self.at(TokenKind::CloseParenthesis) {
+            loop {
+                let param_start = self.token.span.start;
+                self.start_node(NodeKind::Param);
+                let ty = self.parse_type()?;
+                let name = self.expect_ident()?;
+                self.finish_node();
+                params.push(Param {
+                    ty,
+                    name,
+                    span: self.span_from(param_start),
+                });
+                if !self.eat(TokenKind::Comma)? {
+                    break;
+                }
+            }
+        }
+        self.expect(TokenKind::CloseParenthesis)?;
+        self.finish_node();
+
+        let return_type = if self.eat(TokenKind::To)? {
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+        let body = self.parse_body()?;
+        self.finish_node();
+
+        Ok(FunctionDecl {
+            doc,
+            name,
+            params,
+            return_type,
+            body,
+            span: self.span_from(start),
+        })
+    }
+
+    fn parse_type(&mut self) -> Result<Type, ParseError> {
+        let start = self.token.span.start;
+        let base = match self.token.kind {
+            TokenKind::Boolean => BaseType::Boolean,
+            TokenKind::Integer => BaseType::Integer,
+            _ => return Err(self.unexpected("type")),
+        };
+        self.start_node(NodeKind::Type);
+        self.bump()?;
+        let is_array = self.eat(TokenKind::Array)?;
+        self.finish_node();
+
+        Ok(Type {
+            base,
+            is_array,
+            span: self.span_from(start),
+        })
+    }
+
+    fn parse_body(&mut self) -> Result<Body, ParseError> {
+        let start = self.token.span.start;
+
+        self.start_node(NodeKind::Body);
+        self.expect(TokenKind::Begin)?;
+        let mut vars = Vec::new();
+        while self.at(TokenKind::Boolean) || self.at(TokenKind::Integer) {
+            let marker = self.marker();
+            match self.parse_var_decl() {
+                Ok(var) => vars.push(var),
+                Err(err) if self.recover => {
+                    self.report(err);
+                    self.finish_nodes_to(marker);
+                    self.skip_until(|kind| kind == TokenKind::Semicolon || is_sync_point(kind))?;
+                    self.eat(TokenKind::Semicolon)?;
+                    self.start_node_at(marker, NodeKind::Error);
+                    self.finish_node();
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        let statements = self.parse_statements()?;
+        self.expect(TokenKind::End)?;
+        self.finish_node();
+
+        Ok(Body {
+            vars,
+            statements,
+            span: self.span_from(start),
+        })
+    }
+
+    fn parse_var_decl(&mut self) -> Result<VarDecl, ParseError> {
+        let start = self.token.span.start;
+
+        self.start_node(NodeKind::VarDecl);
+        let ty = self.parse_type()?;
+        let mut names = vec![self.expect_ident()?];
+        while self.eat(TokenKind::Comma)? {
+            names.push(self.expect_ident()?);
+        }
+        self.expect(TokenKind::Semicolon)?;
+        self.finish_node();
+
+        Ok(VarDecl {
+            ty,
+            names,
+            span: self.span_from(start),
+        })
+    }
+
+    // "relax" | statement {";" statement}
+    fn parse_statements(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        if self.at(TokenKind::Relax) {
+            let span = self.token.span;
+            self.start_node(NodeKind::RelaxStmt);
+            self.bump()?;
+            self.finish_node();
+            return Ok(vec![Stmt::Relax { span }]);
+        }
+
+        let mut statements = vec![self.parse_statement_or_recover()?];
+        loop {
+            if self.eat(TokenKind::Semicolon)? {
+                statements.push(self.parse_statement_or_recover()?);
+            } else if self.recover && starts_statement(self.token.kind) {
+                // a missing ';' between two statements
+                self.expect(TokenKind::Semicolon)?;
+                statements.push(self.parse_statement_or_recover()?);
+            } else {
+                return Ok(statements);
+            }
+        }
+    }
+
+    // parses a statement, which in recovery mode becomes Stmt::Error if it has
+    // a syntax error that cannot be fixed by inserting a token
+    fn parse_statement_or_recover(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.token.span.start;
+        let marker = self.marker();
+        match self.parse_statement() {
+            Err(err) if self.recover => {
+                self.report(err);
+                self.finish_nodes_to(marker);
+                self.skip_until(is_sync_point)?;
+                self.start_node_at(marker, NodeKind::Error);
+                self.finish_node();
+                Ok(Stmt::Error {
+                    span: self.span_from(start),
+                })
+            }
+            result => result,
+        }
+    }
+
+    fn parse_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.token.span.start;
+
+        match self.token.kind {
+            TokenKind::Id => {
+                self.start_node(NodeKind::AssignStmt);
+                let target = self.expect_ident()?;
+                let index = self.parse_index()?;
+                self.expect(TokenKind::Gets)?;
+                let value = if self.at(TokenKind::Array) {
+                    let array_start = self.token.span.start;
+                    self.start_node(NodeKind::ArrayAlloc);
+                    self.bump()?;
+                    let length = self.parse_simple()?;
+                    self.finish_node();
+                    AssignValue::Array {
+                        length,
+                        span: self.span_from(array_start),
+                    }
+                } else {
+                    AssignValue::Expr(self.parse_expr()?)
+                };
+                self.finish_node();
+
+                Ok(Stmt::Assign {
+                    target,
+                    index,
+                    value,
+                    span: self.span_from(start),
+                })
+            }
+            TokenKind::Call => {
+                self.start_node(NodeKind::CallStmt);
+                self.bump()?;
+                let name = self.expect_ident()?;
+                let args = self.parse_args()?;
+                self.finish_node();
+
+                Ok(Stmt::Call {
+                    name,
+                    args,
+                    span: self.span_from(start),
+                })
+            }
+            TokenKind::If => {
+                self.start_node(NodeKind::IfStmt);
+                let mut branches = Vec::new();
+                loop {
+                    let branch_start = self.token.span.start;
+                    self.bump()?;
+                    let condition = self.parse_expr()?;
+                    self.expect(TokenKind::Then)?;
+                    let body = self.parse_statements()?;
+                    branches.push(IfBranch {
+                        condition,
+                        body,
+                        span: self.span_from(branch_start),
+                    });
+                    if !self.at(TokenKind::Elsif) {
+                        break;
+                    }
+                }
+                let else_branch = if self.eat(TokenKind::Else)? {
+                    Some(self.parse_statements()?)
+                } else {
+                    None
+                };
+                self.expect(TokenKind::End)?;
+                self.finish_node();
+
+                Ok(Stmt::If {
+                    branches,
+                    else_branch,
+                    span: self.span_from(start),
+                })
+            }
+            TokenKind::Get => {
+                self.start_node(NodeKind::GetStmt);
+                self.bump()?;
+                let target = self.expect_ident()?;
+                let index = self.parse_index()?;
+                self.finish_node();
+
+                Ok(Stmt::Get {
+                    target,
+                    index,
+                    span: self.span_from(start),
+                })
+            }
+            TokenKind::Leave => {
+                self.start_node(NodeKind::LeaveStmt);
+                self.bump()?;
+                let value = if starts_expression(self.token.kind) {
+                    Some(self.parse_expr()?)
+                } else {
+                    None
+                };
+                self.finish_node();
+
+                Ok(Stmt::Leave {
+                    value,
+                    span: self.span_from(start),
+                })
+            }
+            TokenKind::Put => {
+                self.start_node(NodeKind::PutStmt);
+                self.bump()?;
+                let value = self.parse_expr()?;
+                self.finish_node();
+
+                Ok(Stmt::Put {
+                    value,
+                    span: self.span_from(start),
+                })
+            }
+            TokenKind::While => {
+                self.start_node(NodeKind::WhileStmt);
+                self.bump()?;
+                let condition = self.parse_expr()?;
+                self.expect(TokenKind::Do)?;
+                let body = self.parse_statements()?;
+                self.expect(TokenKind::End)?;
+                self.finish_node();
+
+                Ok(Stmt::While {
+                    condition,
+                    body,
+                    span: self.span_from(start),
+                })
+            }
+            _ => Err(self.unexpected("statement")),
+        }
+    }
+
+    // ["[" simple "]"], the index of an array element being assigned or read
+    fn parse_index(&mut self) -> Result<Option<Expr>, ParseError> {
+        if !self.at(TokenKind::OpenBracket) {
+            return Ok(None);
+        }
+        self.start_node(NodeKind::Index);
+        self.bump()?;
+        let index = self.parse_simple()?;
+        self.expect(TokenKind::CloseBracket)?;
+        self.finish_node();
+        Ok(Some(index))
+    }
+
+    // "(" [expr {"," expr}] ")"
+    fn parse_args(&mut self) -> Result<Vec<Expr>, ParseError> {
+        self.start_node(NodeKind::ArgList);
+        self.expect(TokenKind::OpenParenthesis)?;
+        let mut args = Vec::new();
+        if !self.at(TokenKind::CloseParenthesis) {
+            loop {
+                args.push(self.parse_expr()?);
+                if !self.eat(TokenKind::Comma)? {
+                    break;
+                }
+            }
+        }
+        self.expect(TokenKind::CloseParenthesis)?;
+        self.finish_node();
+        Ok(args)
+    }
+
+    // consumes the token and moves on to the next one
+    fn bump(&mut self) -> Result<(), ParseError> {
+        if let Some(builder) = &mut self.builder {
+            builder.token(self.token.kind, self.lexer.text(self.token.span));
+        }
+        self.previous_end = self.token.span.end;
+        self.read_token()
+    }
+
+    // reads the next token, remembering any doc comment before it and adding
+    // the trivia before it to the syntax tree
+    fn read_token(&mut self) -> Result<(), ParseError> {
+        self.doc = None;
+        loop {
+            let token = self.lexer.next_token()?;
+            // taken even without a syntax tree, in case the lexer was given
+            // to the parser retaining trivia
+            let trivia = self.lexer.take_trivia();
+            if let Some(builder) = &mut self.builder {
+                for trivia in trivia {
+                    builder.trivia(LeafKind::Trivia(trivia.kind), trivia.text);
+                }
+                if token.kind == TokenKind::DocComment {
+                    let text = self.lexer.text(token.span);
+                    builder.trivia(LeafKind::Token(TokenKind::DocComment), text);
+                }
+            }
+
+            if token.kind != TokenKind::DocComment {
+                self.token = token;
+                return Ok(());
+            }
+            self.doc = token.symbol();
+        }
+    }
+
+    fn start_node(&mut self, kind: NodeKind) {
+        if let Some(builder) = &mut self.builder {
+            builder.start_node(kind);
+        }
+    }
+
+    // starts a node at a marker taken before its first child was parsed
+    fn start_node_at(&mut self, marker: Marker, kind: NodeKind) {
+        if let Some(builder) = &mut self.builder {
+            builder.start_node_at(marker, kind);
+        }
+    }
+
+    fn finish_node(&mut self) {
+        if let Some(builder) = &mut self.builder {
+            builder.finish_node();
+        }
+    }
+
+    // the current place in the syntax tree, to start a node at later or to
+    // go back to after an error
+    fn marker(&mut self) -> Marker {
+        self.builder
+            .as_mut()
+            .map_or_else(Marker::default, GreenBuilder::marker)
+    }
+
+    // finishes the nodes an error left open since `marker`
+    fn finish_nodes_to(&mut self, marker: Marker) {
+        if let Some(builder) = &mut self.builder {
+            builder.finish_nodes_to(marker);
+        }
+    }
+
+    fn at(&self, kind: TokenKind) -> bool {
+        self.token.kind == kind
+    }
+
+    // consumes the token if it is of the given kind
+    fn eat(&mut self, kind: TokenKind) -> Result<bool, ParseError> {
+        if !self.at(kind) {
+            return Ok(false);
+        }
+        self.bump()?;
+        Ok(true)
+    }
+
+    // consumes the token, which must be of the given kind, returning its span.
+    // In recovery mode a missing token is reported and taken to be there,
+    // with an empty span.
+    fn expect(&mut self, kind: TokenKind) -> Result<Span, ParseError> {
+        if !self.at(kind) {
+            let err = self.error(
+                ParseErrorKind::ExpectedToken {
+                    expected: kind,
+                    found: self.token.kind,
+                },
+                self.token.span,
+            );
+            if !self.recover {
+                return Err(err);
+            }
+            self.report(err);
+            return Ok(Span::new(self.token.span.start, self.token.span.start));
+        }
+        let span = self.token.span;
+        if kind != TokenKind::Eof {
+            self.bump()?;
+        }
+        Ok(span)
+    }
+
+    // consumes an identifier. A missing identifier cannot be made up, so it
+    // is an error even in recovery mode.
+    fn expect_ident(&mut self) -> Result<Ident, ParseError> {
+        let token = self.token;
+        if token.kind != TokenKind::Id {
+            return Err(self.error(
+                ParseErrorKind::ExpectedToken {
+                    expected: TokenKind::Id,
+                    found: token.kind,
+                },
+                token.span,
+            ));
+        }
+        self.bump()?;
+        Ok(Ident {
+            symbol: token.symbol().unwrap(),
+            span: token.span,
+        })
+    }
+
+    // skips tokens up to the first one for which `stop` holds, or the end of
+    // the source
+    fn skip_until(&mut self, stop: impl Fn(TokenKind) -> bool) -> Result<(), ParseError> {
+        while !self.at(TokenKind::Eof) && !stop(self.token.kind) {
+            self.bump()?;
+        }
+        Ok(())
+    }
+
+    // records an error in recovery mode, unless it is about an Error token
+    // (which the lexer has reported already) or at the same place as the last
+    // error, which it is most likely a consequence of
+    fn report(&mut self, err: ParseError) {
+        if self.token.kind == TokenKind::Error && err.span == self.token.span {
+            return;
+        }
+        if self
+            .errors
+            .last()
+            .is_some_and(|last| last.span.start == err.span.start)
+        {
+            return;
+        }
+        self.errors.push(err);
+    }
+
+    // the span from `start` up to the end of the last token consumed
+    fn span_from(&self, start: usize) -> Span {
+        Span::new(start, self.previous_end.max(start))
+    }
+
+    // the error for a token that cannot start `expected`
+    fn unexpected(&self, expected: &'static str) -> ParseError {
+        self.error(
+            ParseErrorKind::Expected {
+                expected,
+                found: self.token.kind,
+            },
+            self.token.span,
+        )
+    }
+
+    fn error(&self, kind: ParseErrorKind, span: Span) -> ParseError {
+        ParseError {
+            kind,
+            span,
+            position: self.lexer.source_map().position(span.start),
+            file_name: self.lexer.file_name().map(str::to_string),
+        }
+    }
+}
+
+// whether the parser can carry on from a token of the given kind after
+// skipping a statement with a syntax error
+fn is_sync_point(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Semicolon
+            | TokenKind::End
+            | TokenKind::Begin
+            | TokenKind::Function
+            | TokenKind::Else
+            | TokenKind::Elsif
+    ) || (kind != TokenKind::Id && starts_statement(kind))
+}
+
+// whether a token of the given kind can start a statement
+fn starts_statement(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Id
+            | TokenKind::Call
+            | TokenKind::If
+            | TokenKind::Get
+            | TokenKind::Leave
+            | TokenKind::Put
+            | TokenKind::While
+    )
+}
+
+// whether a token of the given kind can start an expression, which decides if
+// "leave" is followed by one
+fn starts_expression(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Id
+            | TokenKind::Number
+            | TokenKind::StringLiteral
+            | TokenKind::True
+            | TokenKind::False
+            | TokenKind::OpenParenthesis
+            | TokenKind::Not
+            | TokenKind::Minus
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{BinaryOp, UnaryOp};
+    use crate::cst::view::{self, View};
+    use crate::token::TriviaKind;
+
+    fn parse(source: &str) -> Result<(Program, Interner), ParseError> {
+        let mut parser = Parser::new(Lexer::new(source.as_bytes()));
+        let program = parser.parse_program()?;
+        Ok((program, parser.into_interner()))
+    }
+
+    #[test]
+    fn test_parse_program() {
+        let source = "source demo
+{! Adds one. }
+function inc(integer n, boolean array flags) to integer
+begin
+  leave n + 1
+end
+begin
+  integer array a; boolean done, ok;
+  a := array 10;
+  get a[0];
+  if a[0] > 1 then put \"big\" elsif done then relax else call inc(a[0], x) end;
+  while not done do done := true end;
+  leave
+end
+";
+        let (program, interner) = parse(source).unwrap();
+        assert_eq!(interner.resolve(program.name.symbol), "demo");
+        assert_eq!(program.span, Span::new(0, source.trim_end().len()));
+
+        let function = &program.functions[0];
+        assert_eq!(interner.resolve(function.name.symbol), "inc");
+        assert_eq!(interner.resolve(function.doc.unwrap()), "Adds one.");
+        assert_eq!(function.params.len(), 2);
+        assert!(function.params[1].ty.is_array);
+        assert_eq!(function.params[1].ty.base, BaseType::Boolean);
+        assert_eq!(function.return_type.unwrap().base, BaseType::Integer);
+        assert!(function.span.text(source).starts_with("function inc("));
+        assert!(function.span.text(source).ends_with("leave n + 1\nend"));
+
+        let body = &program.body;
+        assert_eq!(body.vars.len(), 2);
+        assert_eq!(body.vars[1].names.len(), 2);
+        assert_eq!(body.vars[1].span.text(source), "boolean done, ok;");
+
+        let statements = &body.statements;
+        assert_eq!(statements.len(), 5);
+        assert!(matches!(
+            statements[0],
+            Stmt::Assign {
+                value: AssignValue::Array { .. },
+                ..
+            }
+        ));
+        assert_eq!(statements[1].span().text(source), "get a[0]");
+        match &statements[2] {
+            Stmt::If {
+                branches,
+                else_branch: Some(else_branch),
+                ..
+            } => {
+                assert_eq!(branches.len(), 2);
+                assert!(matches!(branches[1].body[0], Stmt::Relax { .. }));
+                assert!(matches!(else_branch[0], Stmt::Call { .. }));
+            }
+            stmt => panic!("expected an if statement, found {:?}", stmt),
+        }
+        assert_eq!(
+            statements[3].span().text(source),
+            "while not done do done := true end"
+        );
+        assert!(matches!(statements[4], Stmt::Leave { value: None, .. }));
+    }
+
+    #[test]
+    fn test_parse_expressions() {
+        let source = "source e begin put -a + b * c[i] = f(1, 2) . \"!\" end";
+        let (program, _) = parse(source).unwrap();
+
+        let value = match &program.body.statements[0] {
+            Stmt::Put { value, .. } => value,
+            stmt => panic!("expected a put statement, found {:?}", stmt),
+        };
+        let (left, right) = match value {
+            Expr::Binary {
+                op: BinaryOp::Concatenate,
+                left,
+                right,
+                ..
+            } => (left, right),
+            expr => panic!("expected a concatenation, found {:?}", expr),
+        };
+        assert!(matches!(**right, Expr::String { .. }));
+        assert_eq!(left.span().text(source), "-a + b * c[i] = f(1, 2)");
+
+        let (sum, call) = match &**left {
+            Expr::Binary {
+                op: BinaryOp::Equal,
+                left,
+                right,
+                ..
+            } => (left, right),
+            expr => panic!("expected a relation, found {:?}", expr),
+        };
+        assert!(matches!(**call, Expr::Call { ref args, .. } if args.len() == 2));
+        match &**sum {
+            Expr::Binary {
+                op: BinaryOp::Add,
+                left,
+                right,
+                ..
+            } => {
+                assert!(matches!(
+                    **left,
+                    Expr::Unary {
+                        op: UnaryOp::Negate,
+                        ..
+                    }
+                ));
+                assert!(matches!(
+                    **right,
+                    Expr::Binary {
+                        op: BinaryOp::Multiply,
+                        ..
+                    }
+                ));
+            }
+            expr => panic!("expected a sum, found {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = parse("source e begin x := 1 y := 2 end").unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::ExpectedToken {
+                expected: TokenKind::End,
+                found: TokenKind::Id
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "1:23: expected 'end', but found identifier"
+        );
+
+        let err = parse("source e begin x := end").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "1:21: expected expression, but found 'end'"
+        );
+
+        let err = parse("source e begin relax end end").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "1:26: expected end of file, but found 'end'"
+        );
+
+        let err = parse("source e begin put \"open end").unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::Lex(crate::error::LexErrorKind::UnterminatedString)
+        );
+    }
+
+    #[test]
+    fn test_recovery_reports_all_errors() {
+        let source = "source demo
+function f(integer) to integer
+begin
+  leave 1
+end
+begin
+  integer x, ;
+  x := 1 +;
+  put x x := 2;
+  if x > then put # end;
+  while x do relax end
+end
+";
+        let mut parser = Parser::new(Lexer::new(source.as_bytes())).with_recovery(true);
+        let program = parser.parse_program().unwrap();
+
+        let errors: Vec<String> = parser.errors().iter().map(|err| err.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "2:19: expected identifier, but found ')'",
+                "7:14: expected identifier, but found ';'",
+                "8:11: expected expression, but found ';'",
+                "9:9: expected ';', but found identifier",
+                "10:10: expected expression, but found 'then'",
+                "10:19: illegal character '#' (ASCII #35)",
+            ]
+        );
+
+        assert!(program.functions.is_empty());
+        let statements = &program.body.statements;
+        assert_eq!(statements.len(), 5);
+        match &statements[0] {
+            Stmt::Assign {
+                value: AssignValue::Expr(Expr::Binary { right, .. }),
+                ..
+            } => assert!(matches!(**right, Expr::Error { .. })),
+            stmt => panic!("expected an assignment, found {:?}", stmt),
+        }
+        assert_eq!(statements[2].span().text(source), "x := 2");
+        assert!(matches!(statements[4], Stmt::While { .. }));
+    }
+
+    #[test]
+    fn test_recovery_skips_to_sync_point() {
+        let source = "source e begin x := 1; ) ] 5 ; put 1; get 3 end";
+        let mut parser = Parser::new(Lexer::new(source.as_bytes())).with_recovery(true);
+        let program = parser.parse_program().unwrap();
+
+        assert_eq!(parser.errors().len(), 2);
+        let statements = &program.body.statements;
+        assert_eq!(statements.len(), 4);
+        assert_eq!(
+            statements[1],
+            Stmt::Error {
+                span: Span::new(23, 28)
+            }
+        );
+        assert!(matches!(statements[2], Stmt::Put { .. }));
+        assert!(matches!(statements[3], Stmt::Error { .. }));
+    }
+
+    #[test]
+    fn test_syntax_tree() {
+        let source = "source demo // the demo
+{! Adds one. }
+function inc(integer n) to integer
+begin
+  leave -n + (1)
+end
+begin
+\tinteger array a;
+\ta := array 10; get a[0];
+\tput a[0] . \"!\" // done
+end
+";
+        let mut parser = Parser::new(Lexer::new(source.as_bytes())).with_syntax_tree();
+        let program = parser.parse_program().unwrap();
+        let root = parser.syntax_tree().unwrap();
+
+        assert_eq!(root.kind(), NodeKind::Program);
+        assert_eq!(root.text(), source);
+        assert!(root.tokens().iter().any(|token| token.kind()
+            == LeafKind::Trivia(TriviaKind::Comment)
+            && token.text() == "// the demo"));
+        assert!(root
+            .tokens()
+            .iter()
+            .any(|token| token.kind() == LeafKind::Token(TokenKind::DocComment)));
+
+        // the views of the syntax tree match the AST, span for span
+        let view = view::Program::cast(root).unwrap();
+        let function = &program.functions[0];
+        let function_view = &view.functions()[0];
+        assert_eq!(function_view.syntax().span(), function.span);
+        assert_eq!(function_view.name().unwrap().text(), "inc");
+        let param = &function_view.param_list().unwrap().params()[0];
+        assert_eq!(param.ty().unwrap().base(), Some(BaseType::Integer));
+        assert!(function_view.return_type().is_some());
+
+        let body = view.body().unwrap();
+        assert_eq!(body.syntax().span(), program.body.span);
+        let var = &body.vars()[0];
+        assert_eq!(var.syntax().text(), "integer array a;");
+        assert!(var.ty().unwrap().is_array());
+        let statements = body.statements();
+        assert_eq!(statements.len(), program.body.statements.len());
+        for (stmt, stmt_view) in program.body.statements.iter().zip(&statements) {
+            assert_eq!(stmt_view.syntax().span(), stmt.span());
+        }
+        assert!(matches!(&statements[1], view::Stmt::Get(get) if get.index().is_some()));
+
+        let value = match &function_view.body().unwrap().statements()[0] {
+            view::Stmt::Leave(leave) => leave.value().unwrap(),
+            stmt => panic!("expected a leave statement, found {:?}", stmt),
+        };
+        let view::Expr::Binary(binary) = value else {
+            panic!("expected a binary expression, found {:?}", value);
+        };
+        assert_eq!(binary.syntax().text(), "-n + (1)");
+        assert_eq!(binary.op(), Some(BinaryOp::Add));
+        let view::Expr::Unary(negation) = binary.left().unwrap() else {
+            panic!("expected a negation");
+        };
+        assert_eq!(negation.op(), Some(UnaryOp::Negate));
+        assert!(matches!(binary.right(), Some(view::Expr::Paren(_))));
+    }
+
+    #[test]
+    fn test_syntax_tree_if_view() {
+        let source = "source e begin
+if a then x := 1 elsif b then relax else if c then relax end; get y end
+end";
+        let mut parser = Parser::new(Lexer::new(source.as_bytes())).with_syntax_tree();
+        parser.parse_program().unwrap();
+        let root = parser.syntax_tree().unwrap();
+        let body = view::Program::cast(root).unwrap().body().unwrap();
+
+        let view::Stmt::If(stmt) = &body.statements()[0] else {
+            panic!("expected an if statement");
+        };
+        let branches = stmt.branches();
+        let texts: Vec<(String, Vec<String>)> = branches
+            .iter()
+            .map(|branch| {
+                let condition = branch.condition.as_ref().unwrap().syntax().text();
+                let statements = branch
+                    .statements
+                    .iter()
+                    .map(|stmt| stmt.syntax().text().trim().to_string())
+                    .collect();
+                (condition.trim().to_string(), statements)
+            })
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                (String::from("a"), vec![String::from("x := 1")]),
+                (String::from("b"), vec![String::from("relax")]),
+            ]
+        );
+        let else_statements = stmt.else_statements().unwrap();
+        assert_eq!(else_statements.len(), 2);
+        assert!(matches!(else_statements[0], view::Stmt::If(_)));
+    }
+
+    #[test]
+    fn test_syntax_tree_with_recovery() {
+        let source = "source e begin x := 1; ) ] 5 ; put (1 +); get 3 end end";
+        let mut parser = Parser::new(Lexer::new(source.as_bytes()))
+            .with_recovery(true)
+            .with_syntax_tree();
+        let program = parser.parse_program().unwrap();
+        let root = parser.syntax_tree().unwrap();
+
+        assert_eq!(root.text(), source);
+        let body = view::Program::cast(root.clone()).unwrap().body().unwrap();
+        let statements = body.statements();
+        assert_eq!(statements.len(), program.body.statements.len());
+        let view::Stmt::Error(error) = &statements[1] else {
+            panic!("expected an error node, found {:?}", statements[1]);
+        };
+        assert_eq!(error.syntax().span(), program.body.statements[1].span());
+        assert_eq!(error.syntax().text(), ") ] 5");
+        let view::Stmt::Put(put) = &statements[2] else {
+            panic!("expected a put statement, found {:?}", statements[2]);
+        };
+        let view::Expr::Paren(paren) = put.value().unwrap() else {
+            panic!("expected a parenthesized expression");
+        };
+        let view::Expr::Binary(binary) = paren.expr().unwrap() else {
+            panic!("expected a binary expression");
+        };
+        assert!(matches!(binary.right(), Some(view::Expr::Error(_))));
+        let errors: Vec<_> = root
+            .descendants()
+            .into_iter()
+            .filter(|node| node.kind() == NodeKind::Error)
+            .map(|node| node.text())
+            .collect();
+        assert_eq!(errors, [") ] 5", "", "get 3", "end"]);
+    }
+}
@@ -0,0 +1,452 @@
+use super::{is_sync_point, Parser};
+use crate::ast::{BinaryOp, Expr, Precedence, UnaryOp};
+use crate::cst::{Marker, NodeKind};
+use crate::error::{ParseError, ParseErrorKind};
+use crate::source::Source;
+use crate::token::TokenKind;
+
+// expressions are parsed by precedence climbing over the operator groups of
+// token.rs, loosest first:
+//
+//   concatenation   .                   left-associative
+//   relational      = >= > <= < <>      non-associative
+//   additive        - or +              left-associative
+//   multiplicative  and / * rem         left-associative
+//
+// A leading "-" negates the first term of an additive expression, so it binds
+// looser than the multiplicative operators and is only allowed where an
+// additive expression starts. "not" applies to a single factor.
+impl<'a, S: Source> Parser<'a, S> {
+    // expr = relation {"." relation}
+    pub(super) fn parse_expr(&mut self) -> Result<Expr, ParseError> {
+        self.parse_binary(Precedence::Concatenation)
+    }
+
+    // simple = ["-"] term {addop term}, as used for array indices and lengths
+    pub(super) fn parse_simple(&mut self) -> Result<Expr, ParseError> {
+        self.parse_binary(Precedence::Additive)
+    }
+
+    // parses operands joined by binary operators that bind at least as
+    // tightly as `min`
+    fn parse_binary(&mut self, min: Precedence) -> Result<Expr, ParseError> {
+        let start = self.token.span.start;
+        let marker = self.marker();
+        let left = self.parse_operand(min)?;
+        self.parse_binary_from(start, marker, left, min)
+    }
+
+    // carries on parsing after the leftmost operand `left`, whose text (and any
+    // parentheses around it) starts at `start`, and whose syntax node starts
+    // at `marker`
+    fn parse_binary_from(
+        &mut self,
+        start: usize,
+        marker: Marker,
+        mut left: Expr,
+        min: Precedence,
+    ) -> Result<Expr, ParseError> {
+        while let Some(op) = binary_op(self.token.kind) {
+            let precedence = op.precedence();
+            if precedence < min {
+                break;
+            }
+            self.start_node_at(marker, NodeKind::BinaryExpr);
+            self.bump()?;
+
+            // the right operand only takes tighter operators, which makes the
+            // operator group to the left
+            let right = self.parse_binary(precedence.tighter())?;
+            self.finish_node();
+            left = Expr::Binary {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+                span: self.span_from(start),
+            };
+
+            if !op.is_associative()
+                && binary_op(self.token.kind).is_some_and(|next| next.precedence() == precedence)
+            {
+                return Err(self.error(ParseErrorKind::ChainedComparison, self.token.span));
+            }
+        }
+        Ok(left)
+    }
+
+    // an operand of operators binding at least as tightly as `min`, which is a
+    // negated term where an additive expression may start
+    fn parse_operand(&mut self, min: Precedence) -> Result<Expr, ParseError> {
+        if !(self.at(TokenKind::Minus) && min <= Precedence::Additive) {
+            return self.parse_factor();
+        }
+
+        let start = self.token.span.start;
+        self.start_node(NodeKind::UnaryExpr);
+        self.bump()?;
+
+        // the smallest integer can only be written as a negated literal, and
+        // only on its own, as "-" would otherwise apply to a product with it
+        let token = self.token;
+        let operand = if token.number() == Some(self.max_magnitude()) {
+            self.bump_literal()?;
+            if binary_op(self.token.kind)
+                .is_some_and(|op| op.precedence() == Precedence::Multiplicative)
+            {
+                return Err(self.error(ParseErrorKind::NumberTooLarge, token.span));
+            }
+            Expr::Number {
+                value: token.number().unwrap(),
+                span: token.span,
+            }
+        } else {
+            self.parse_binary(Precedence::Multiplicative)?
+        };
+        self.finish_node();
+
+        Ok(Expr::Unary {
+            op: UnaryOp::Negate,
+            operand: Box::new(operand),
+            span: self.span_from(start),
+        })
+    }
+
+    // factor = id ["[" simple "]" | "(" [expr {"," expr}] ")"] | num | string
+    //        | "(" expr ")" | "not" factor | "true" | "false"
+    fn parse_factor(&mut self) -> Result<Expr, ParseError> {
+        let start = self.token.span.start;
+        let token = self.token;
+
+        match token.kind {
+            TokenKind::Id => {
+                let marker = self.marker();
+                let name = self.expect_ident()?;
+                if self.at(TokenKind::OpenBracket) {
+                    self.start_node_at(marker, NodeKind::IndexExpr);
+                    let index = self.parse_index()?.unwrap();
+                    self.finish_node();
+                    Ok(Expr::Index {
+                        array: name,
+                        index: Box::new(index),
+                        span: self.span_from(start),
+                    })
+                } else if self.at(TokenKind::OpenParenthesis) {
+                    self.start_node_at(marker, NodeKind::CallExpr);
+                    let args = self.parse_args()?;
+                    self.finish_node();
+                    Ok(Expr::Call {
+                        name,
+                        args,
+                        span: self.span_from(start),
+                    })
+                } else {
+                    self.start_node_at(marker, NodeKind::NameRef);
+                    self.finish_node();
+                    Ok(Expr::Variable(name))
+                }
+            }
+            TokenKind::Number => {
+                let value = token.number().unwrap();
+                if value >= self.max_magnitude() {
+                    return Err(self.error(ParseErrorKind::NumberTooLarge, token.span));
+                }
+                self.bump_literal()?;
+                Ok(Expr::Number {
+                    value,
+                    span: token.span,
+                })
+            }
+            TokenKind::StringLiteral => {
+                self.bump_literal()?;
+                Ok(Expr::String {
+                    value: token.symbol().unwrap(),
+                    span: token.span,
+                })
+            }
+            TokenKind::True | TokenKind::False => {
+                self.bump_literal()?;
+                Ok(Expr::Boolean {
+                    value: token.kind == TokenKind::True,
+                    span: token.span,
+                })
+            }
+            TokenKind::OpenParenthesis => {
+                self.start_node(NodeKind::ParenExpr);
+                self.bump()?;
+                let expr = self.parse_expr()?;
+                self.expect(TokenKind::CloseParenthesis)?;
+                self.finish_node();
+                Ok(expr)
+            }
+            TokenKind::Not => {
+                self.start_node(NodeKind::UnaryExpr);
+                self.bump()?;
+                let operand = self.parse_factor()?;
+                self.finish_node();
+                Ok(Expr::Unary {
+                    op: UnaryOp::Not,
+                    operand: Box::new(operand),
+                    span: self.span_from(start),
+                })
+            }
+            _ => {
+                let err = self.unexpected("expression");
+                if !self.recover {
+                    return Err(err);
+                }
+
+                // skip the token unless the parser can carry on from it
+                self.report(err);
+                self.start_node(NodeKind::Error);
+                if !is_sync_point(token.kind) && !closes_expression(token.kind) {
+                    self.bump()?;
+                }
+                self.finish_node();
+                Ok(Expr::Error {
+                    span: self.span_from(start),
+                })
+            }
+        }
+    }
+
+    // consumes a literal token as a Literal node
+    fn bump_literal(&mut self) -> Result<(), ParseError> {
+        self.start_node(NodeKind::Literal);
+        self.bump()?;
+        self.finish_node();
+        Ok(())
+    }
+
+    // the value of the number literal that is one past the largest integer
+    fn max_magnitude(&self) -> u64 {
+        self.lexer.options().integer_width.max_magnitude()
+    }
+}
+
+// whether a token of the given kind can follow an expression, so that the
+// parser should not skip it when an expression is missing before it
+fn closes_expression(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::CloseParenthesis
+            | TokenKind::CloseBracket
+            | TokenKind::Comma
+            | TokenKind::Then
+            | TokenKind::Do
+            | TokenKind::Eof
+    )
+}
+
+// the binary operator a token stands for, if any
+pub(crate) fn binary_op(kind: TokenKind) -> Option<BinaryOp> {
+    match kind {
+        TokenKind::Concatenate => Some(BinaryOp::Concatenate),
+        TokenKind::Equal => Some(BinaryOp::Equal),
+        TokenKind::GreaterEqual => Some(BinaryOp::GreaterEqual),
+        TokenKind::GreaterThan => Some(BinaryOp::GreaterThan),
+        TokenKind::LessEqual => Some(BinaryOp::LessEqual),
+        TokenKind::LessThan => Some(BinaryOp::LessThan),
+        TokenKind::NotEqual => Some(BinaryOp::NotEqual),
+        TokenKind::Minus => Some(BinaryOp::Subtract),
+        TokenKind::Or => Some(BinaryOp::Or),
+        TokenKind::Plus => Some(BinaryOp::Add),
+        TokenKind::And => Some(BinaryOp::And),
+        TokenKind::Divide => Some(BinaryOp::Divide),
+        TokenKind::Multiply => Some(BinaryOp::Multiply),
+        TokenKind::Remainder => Some(BinaryOp::Remainder),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::LexErrorKind;
+    use crate::intern::Interner;
+    use crate::lexer::{IntegerWidth, Lexer};
+
+    // the expression written with full parentheses, operators being named by
+    // their tokens and negation by "neg"
+    fn render(expr: &Expr, interner: &Interner) -> String {
+        match expr {
+            Expr::Variable(ident) => interner.resolve(ident.symbol).to_string(),
+            Expr::Number { value, .. } => value.to_string(),
+            Expr::Boolean { value, .. } => value.to_string(),
+            Expr::String { value, .. } => format!("{:?}", interner.resolve(*value)),
+            Expr::Unary { op, operand, .. } => {
+                let op = match op {
+                    UnaryOp::Negate => "neg",
+                    UnaryOp::Not => "not",
+                };
+                format!("({} {})", op, render(operand, interner))
+            }
+            Expr::Binary {
+                op, left, right, ..
+            } => {
+                let op = TokenKind::ALL
+                    .iter()
+                    .find(|kind| binary_op(**kind) == Some(*op))
+                    .unwrap();
+                format!(
+                    "({} {} {})",
+                    render(left, interner),
+                    op.text().unwrap(),
+                    render(right, interner)
+                )
+            }
+            Expr::Index { array, index, .. } => format!(
+                "{}[{}]",
+                interner.resolve(array.symbol),
+                render(index, interner)
+            ),
+            Expr::Call { name, args, .. } => {
+                let args: Vec<String> = args.iter().map(|arg| render(arg, interner)).collect();
+                format!("{}({})", interner.resolve(name.symbol), args.join(", "))
+            }
+            Expr::Error { .. } => "<error>".to_string(),
+        }
+    }
+
+    fn parse_with(source: &str, lexer: Lexer) -> Result<String, ParseError> {
+        let mut parser = Parser::new(lexer);
+        parser.read_token()?;
+        let expr = parser.parse_expr()?;
+        parser.expect(TokenKind::Eof)?;
+        assert_eq!(expr.span().text(source), source.trim());
+        Ok(render(&expr, parser.interner()))
+    }
+
+    fn parse(source: &str) -> Result<String, ParseError> {
+        parse_with(source, Lexer::new(source.as_bytes()))
+    }
+
+    fn parse_err(source: &str) -> ParseErrorKind {
+        parse(source).unwrap_err().kind
+    }
+
+    #[test]
+    fn test_binary_op_matches_token_groups() {
+        // the operator tokens in declaration order, split where the precedence
+        // of their operators changes, which should give the groups of token.rs
+        let mut groups: Vec<(Precedence, Vec<TokenKind>)> = Vec::new();
+        let mut ops = Vec::new();
+        for kind in TokenKind::ALL {
+            let Some(op) = binary_op(*kind) else {
+                continue;
+            };
+            assert!(!ops.contains(&op), "{:?} has two tokens", op);
+            ops.push(op);
+            match groups.last_mut() {
+                Some((precedence, kinds)) if *precedence == op.precedence() => kinds.push(*kind),
+                _ => groups.push((op.precedence(), vec![*kind])),
+            }
+        }
+
+        let precedences: Vec<Precedence> =
+            groups.iter().map(|(precedence, _)| *precedence).collect();
+        assert_eq!(
+            precedences,
+            [
+                Precedence::Relational,
+                Precedence::Additive,
+                Precedence::Multiplicative,
+                Precedence::Concatenation,
+            ]
+        );
+        assert_eq!(binary_op(TokenKind::Not), None);
+    }
+
+    #[test]
+    fn test_precedence_boundaries() {
+        // concatenation < relational
+        assert_eq!(parse("a = b . c < d").unwrap(), "((a = b) . (c < d))");
+        // relational < additive
+        assert_eq!(parse("a + b >= c - d").unwrap(), "((a + b) >= (c - d))");
+        // additive < multiplicative
+        assert_eq!(parse("a or b and c").unwrap(), "(a or (b and c))");
+        assert_eq!(parse("a * b + c rem d").unwrap(), "((a * b) + (c rem d))");
+        // multiplicative < not
+        assert_eq!(parse("not a and b").unwrap(), "((not a) and b)");
+        assert_eq!(parse("not not a").unwrap(), "(not (not a))");
+        // parentheses override precedence
+        assert_eq!(parse("(a + b) * c").unwrap(), "((a + b) * c)");
+        assert_eq!(parse("a * (b . c)").unwrap(), "(a * (b . c))");
+    }
+
+    #[test]
+    fn test_associativity() {
+        assert_eq!(parse("a . b . c").unwrap(), "((a . b) . c)");
+        assert_eq!(parse("a - b + c or d").unwrap(), "(((a - b) + c) or d)");
+        assert_eq!(parse("a / b * c rem d").unwrap(), "(((a / b) * c) rem d)");
+
+        assert_eq!(parse_err("a < b < c"), ParseErrorKind::ChainedComparison);
+        assert_eq!(parse_err("a = b <> c"), ParseErrorKind::ChainedComparison);
+        assert_eq!(parse("(a < b) = c").unwrap(), "((a < b) = c)");
+    }
+
+    #[test]
+    fn test_unary_minus_negates_first_term() {
+        assert_eq!(parse("-a * b + c").unwrap(), "((neg (a * b)) + c)");
+        assert_eq!(parse("a = -b").unwrap(), "(a = (neg b))");
+        assert_eq!(parse("a . -b").unwrap(), "(a . (neg b))");
+        assert_eq!(parse("-(a + b)").unwrap(), "(neg (a + b))");
+
+        // a "-" cannot start the operand of an additive or multiplicative
+        // operator
+        for source in ["a + -b", "a * -b", "--a", "not -a"] {
+            assert!(matches!(
+                parse_err(source),
+                ParseErrorKind::Expected {
+                    expected: "expression",
+                    found: TokenKind::Minus
+                }
+            ));
+        }
+    }
+
+    #[test]
+    fn test_index_and_call() {
+        assert_eq!(parse("a[i + 1] * f()").unwrap(), "(a[(i + 1)] * f())");
+        assert_eq!(
+            parse("f(a[0], g(b) . \"x\", -c)").unwrap(),
+            "f(a[0], (g(b) . \"x\"), (neg c))"
+        );
+        assert_eq!(parse("not a[-i]").unwrap(), "(not a[(neg i)])");
+
+        // an index is a simple expression, without relational operators
+        assert!(matches!(
+            parse_err("a[i = 1]"),
+            ParseErrorKind::ExpectedToken {
+                expected: TokenKind::CloseBracket,
+                found: TokenKind::Equal
+            }
+        ));
+    }
+
+    #[test]
+    fn test_smallest_integer_literal() {
+        assert_eq!(parse("-2147483648").unwrap(), "(neg 2147483648)");
+        assert_eq!(parse("2147483647").unwrap(), "2147483647");
+        // the lexer rejects the literal unless it follows a '-', and the
+        // parser when that '-' is not a negation
+        let too_large = ParseErrorKind::Lex(LexErrorKind::NumberTooLarge);
+        assert_eq!(parse_err("2147483648"), too_large);
+        assert_eq!(parse_err("-(2147483648)"), too_large);
+        assert_eq!(parse_err("a - 2147483648"), ParseErrorKind::NumberTooLarge);
+        assert_eq!(parse("-2147483648 + 1").unwrap(), "((neg 2147483648) + 1)");
+        assert_eq!(parse("-2147483648 < a").unwrap(), "((neg 2147483648) < a)");
+        for source in [
+            "-2147483648 / 2",
+            "-2147483648 * 2",
+            "-2147483648 rem 2",
+            "-2147483648 and a",
+        ] {
+            assert_eq!(parse_err(source), ParseErrorKind::NumberTooLarge);
+        }
+
+        let source = "9223372036854775808";
+        let lexer = Lexer::new(source.as_bytes()).with_integer_width(IntegerWidth::I64);
+        assert_eq!(parse_with(source, lexer).unwrap_err().kind, too_large);
+        assert!(parse("2147483648 . x").is_err());
+    }
+}
@@ -0,0 +1,471 @@
+use std::collections::HashMap;
+
+use crate::ast::visit::{self, Visitor};
+use crate::ast::{Body, Expr, FunctionDecl, Ident, Param, Program, Stmt, Type, VarDecl};
+use crate::error::{ResolveError, ResolveErrorKind};
+use crate::intern::{Interner, Symbol};
+use crate::span::{SourceMap, Span};
+
+// Name resolution binds every identifier to the function, parameter or
+// variable it names. The scopes, innermost last, are:
+//
+// - the program, holding every function, so that functions can call each
+//   other (and themselves) whatever order they are declared in
+// - the parameters of a function
+// - the variables of a body, either of a function or of the program
+//
+// A name may be declared once in each scope, and a declaration in an inner
+// scope hides (with a warning) one in an enclosing scope.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+// a handle to a declaration in a Resolution
+pub struct DeclId(u32);
+
+impl DeclId {
+    pub fn as_u32(self) -> u32 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeclKind {
+    Function {
+        params: Vec<Type>,
+        // None for a procedure
+        return_type: Option<Type>,
+    },
+    Param(Type),
+    Variable(Type),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration {
+    pub name: Ident,
+    pub kind: DeclKind,
+}
+
+impl Declaration {
+    pub fn is_function(&self) -> bool {
+        matches!(self.kind, DeclKind::Function { .. })
+    }
+
+    // the type of a parameter or variable
+    pub fn ty(&self) -> Option<Type> {
+        match self.kind {
+            DeclKind::Param(ty) | DeclKind::Variable(ty) => Some(ty),
+            DeclKind::Function { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+// the declarations of a program and what each of its identifiers refers to
+pub struct Resolution {
+    declarations: Vec<Declaration>,
+
+    // the declaration of every identifier that could be resolved, by the
+    // span of the identifier, declarations included
+    bindings: HashMap<Span, DeclId>,
+
+    // the errors and warnings, in the order they appear in the source
+    errors: Vec<ResolveError>,
+}
+
+impl Resolution {
+    pub fn declaration(&self, id: DeclId) -> &Declaration {
+        &self.declarations[id.0 as usize]
+    }
+
+    // every declaration, in the order in which they were resolved
+    pub fn declarations(&self) -> &[Declaration] {
+        &self.declarations
+    }
+
+    // the declaration an identifier of the program refers to, if it was
+    // resolved
+    pub fn binding(&self, ident: Ident) -> Option<DeclId> {
+        self.bindings.get(&ident.span).copied()
+    }
+
+    pub fn lookup(&self, ident: Ident) -> Option<&Declaration> {
+        self.binding(ident).map(|id| self.declaration(id))
+    }
+
+    pub fn errors(&self) -> &[ResolveError] {
+        &self.errors
+    }
+
+    // whether any error other than a warning was reported
+    pub fn has_errors(&self) -> bool {
+        self.errors.iter().any(|err| !err.kind.is_warning())
+    }
+}
+
+// resolves the names of a program, see `resolve`
+pub struct Resolver<'a> {
+    interner: &'a Interner,
+    source_map: &'a SourceMap,
+    file_name: Option<String>,
+
+    // the declarations visible in every scope, innermost last
+    scopes: Vec<HashMap<Symbol, DeclId>>,
+
+    resolution: Resolution,
+}
+
+impl<'a> Resolver<'a> {
+    // a resolver for a program parsed into `interner`, whose source is
+    // described by `source_map`
+    pub fn new(interner: &'a Interner, source_map: &'a SourceMap) -> Self {
+        Resolver {
+            interner,
+            source_map,
+            file_name: None,
+            scopes: Vec::new(),
+            resolution: Resolution::default(),
+        }
+    }
+
+    // sets the file name reported in errors
+    pub fn with_file_name(mut self, file_name: &str) -> Self {
+        self.file_name = Some(file_name.to_string());
+        self
+    }
+
+    // resolves every identifier of the program, reporting all errors rather
+    // than stopping at the first
+    pub fn resolve(mut self, program: &Program) -> Resolution {
+        self.visit_program(program);
+        self.resolution.errors.sort_by_key(|err| err.span.start);
+        self.resolution
+    }
+
+    fn declare(&mut self, name: Ident, kind: DeclKind) {
+        let id = DeclId(self.resolution.declarations.len() as u32);
+        self.resolution
+            .declarations
+            .push(Declaration { name, kind });
+        self.resolution.bindings.insert(name.span, id);
+
+        let (scope, outer) = self.scopes.split_last_mut().unwrap();
+        if let Some(&previous) = scope.get(&name.symbol) {
+            let kind = ResolveErrorKind::Duplicate {
+                name: self.name(name),
+                previous: self.resolution.declaration(previous).name.span,
+            };
+            self.error(kind, name.span);
+            return;
+        }
+        scope.insert(name.symbol, id);
+
+        let shadowed = outer
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name.symbol).copied());
+        if let Some(shadowed) = shadowed {
+            let kind = ResolveErrorKind::Shadowing {
+                name: self.name(name),
+                shadowed: self.resolution.declaration(shadowed).name.span,
+            };
+            self.error(kind, name.span);
+        }
+    }
+
+    // binds a use of a name to its declaration, reporting it if there is none
+    fn resolve_use(&mut self, name: Ident) -> Option<&Declaration> {
+        let id = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name.symbol).copied());
+        match id {
+            Some(id) => {
+                self.resolution.bindings.insert(name.span, id);
+                Some(self.resolution.declaration(id))
+            }
+            None => {
+                let kind = ResolveErrorKind::Undeclared(self.name(name));
+                self.error(kind, name.span);
+                None
+            }
+        }
+    }
+
+    // resolves a name that must be a variable or parameter, which is
+    // `assigned` to or read
+    fn resolve_variable(&mut self, name: Ident, assigned: bool) {
+        let declaration = match self.resolve_use(name) {
+            Some(declaration) if declaration.is_function() => declaration.name.span,
+            _ => return,
+        };
+        let name_text = self.name(name);
+        let kind = if assigned {
+            ResolveErrorKind::AssignToFunction {
+                name: name_text,
+                declaration,
+            }
+        } else {
+            ResolveErrorKind::FunctionAsVariable {
+                name: name_text,
+                declaration,
+            }
+        };
+        self.error(kind, name.span);
+    }
+
+    // resolves a name that must be a function
+    fn resolve_function(&mut self, name: Ident) {
+        let declaration = match self.resolve_use(name) {
+            Some(declaration) if !declaration.is_function() => declaration.name.span,
+            _ => return,
+        };
+        let kind = ResolveErrorKind::CallToVariable {
+            name: self.name(name),
+            declaration,
+        };
+        self.error(kind, name.span);
+    }
+
+    fn name(&self, ident: Ident) -> String {
+        self.interner.resolve(ident.symbol).to_string()
+    }
+
+    fn error(&mut self, kind: ResolveErrorKind, span: Span) {
+        self.resolution.errors.push(ResolveError {
+            kind,
+            span,
+            position: self.source_map.position(span.start),
+            file_name: self.file_name.clone(),
+        });
+    }
+}
+
+impl Visitor for Resolver<'_> {
+    fn visit_program(&mut self, program: &Program) {
+        // the functions are declared up front, so that they can be called
+        // before their declaration
+        self.scopes.push(HashMap::new());
+        for function in &program.functions {
+            let kind = DeclKind::Function {
+                params: function.params.iter().map(|param| param.ty).collect(),
+                return_type: function.return_type,
+            };
+            self.declare(function.name, kind);
+        }
+        visit::walk_program(self, program);
+        self.scopes.pop();
+    }
+
+    fn visit_function(&mut self, function: &FunctionDecl) {
+        // the name was declared with the other functions
+        self.scopes.push(HashMap::new());
+        for param in &function.params {
+            self.visit_param(param);
+        }
+        self.visit_body(&function.body);
+        self.scopes.pop();
+    }
+
+    fn visit_param(&mut self, param: &Param) {
+        self.declare(param.name, DeclKind::Param(param.ty));
+    }
+
+    fn visit_body(&mut self, body: &Body) {
+        self.scopes.push(HashMap::new());
+        visit::walk_body(self, body);
+        self.scopes.pop();
+    }
+
+    fn visit_var_decl(&mut self, var: &VarDecl) {
+        for name in &var.names {
+            self.declare(*name, DeclKind::Variable(var.ty));
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Assign { target, .. } | Stmt::Get { target, .. } => {
+                self.resolve_variable(*target, true)
+            }
+            Stmt::Call { name, .. } => self.resolve_function(*name),
+            _ => {}
+        }
+        visit::walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Variable(name) | Expr::Index { array: name, .. } => {
+                self.resolve_variable(*name, false)
+            }
+            Expr::Call { name, .. } => self.resolve_function(*name),
+            _ => {}
+        }
+        visit::walk_expr(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::BaseType;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn resolve(source: &str) -> (Program, Resolution) {
+        let mut parser = Parser::new(Lexer::new(source.as_bytes()));
+        let program = parser.parse_program().unwrap();
+        let resolution = Resolver::new(parser.interner(), parser.source_map()).resolve(&program);
+        (program, resolution)
+    }
+
+    // the errors as "text of the span: message"
+    fn errors(source: &str) -> Vec<String> {
+        let (_, resolution) = resolve(source);
+        resolution
+            .errors()
+            .iter()
+            .map(|err| format!("{}: {}", err.span.text(source), err.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_resolve_binds_uses() {
+        let source = "source demo
+function twice(integer n) to integer begin leave double(n) end
+function double(integer n) to integer begin leave n + n end
+begin
+  integer array a; integer i;
+  a := array 3; get a[0];
+  i := twice(a[0]); call double(i)
+end";
+        let (program, resolution) = resolve(source);
+        assert!(resolution.errors().is_empty());
+
+        // `double` is called before it is declared
+        let call = match &program.functions[0].body.statements[0] {
+            Stmt::Leave {
+                value: Some(Expr::Call { name, args, .. }),
+                ..
+            } => {
+                let arg = match &args[0] {
+                    Expr::Variable(arg) => *arg,
+                    expr => panic!("expected a variable, found {:?}", expr),
+                };
+                assert_eq!(
+                    resolution.binding(arg),
+                    resolution.binding(program.functions[0].params[0].name)
+                );
+                *name
+            }
+            stmt => panic!("expected a leave statement, found {:?}", stmt),
+        };
+        let double = resolution.lookup(call).unwrap();
+        assert_eq!(double.name, program.functions[1].name);
+        assert_eq!(
+            double.kind,
+            DeclKind::Function {
+                params: vec![program.functions[1].params[0].ty],
+                return_type: program.functions[1].return_type,
+            }
+        );
+
+        // the parameters of different functions are different declarations
+        assert_ne!(
+            resolution.binding(program.functions[0].params[0].name),
+            resolution.binding(program.functions[1].params[0].name)
+        );
+
+        let target = match &program.body.statements[1] {
+            Stmt::Get { target, .. } => *target,
+            stmt => panic!("expected a get statement, found {:?}", stmt),
+        };
+        let ty = resolution.lookup(target).unwrap().ty().unwrap();
+        assert_eq!((ty.base, ty.is_array), (BaseType::Integer, true));
+        assert_eq!(resolution.declarations().len(), 6);
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        let source = "source e
+function f(integer n, boolean n) begin relax end
+function f() begin relax end
+begin
+  integer x, x;
+  y := 1;
+  f := 2;
+  get f;
+  call x();
+  put f + g(1)
+end";
+        assert_eq!(
+            errors(source),
+            [
+                "n: 'n' is already declared",
+                "f: 'f' is already declared",
+                "x: 'x' is already declared",
+                "y: 'y' is not declared",
+                "f: cannot assign to function 'f'",
+                "f: cannot assign to function 'f'",
+                "x: 'x' is a variable, not a function",
+                "f: 'f' is a function, not a variable",
+                "g: 'g' is not declared",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_shadowing() {
+        let source = "source e
+function f(integer n) to integer
+begin
+  integer n, f;
+  leave n
+end
+begin
+  integer f;
+  f := 1
+end";
+        let (program, resolution) = resolve(source);
+        let messages: Vec<_> = resolution
+            .errors()
+            .iter()
+            .map(|err| (err.position.line, err.kind.to_string()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (4, "'n' shadows an earlier declaration".to_string()),
+                (4, "'f' shadows an earlier declaration".to_string()),
+                (8, "'f' shadows an earlier declaration".to_string()),
+            ]
+        );
+        assert!(!resolution.has_errors());
+
+        // the innermost declaration is the one used
+        let function = &program.functions[0];
+        let value = match &function.body.statements[0] {
+            Stmt::Leave {
+                value: Some(Expr::Variable(value)),
+                ..
+            } => *value,
+            stmt => panic!("expected a leave statement, found {:?}", stmt),
+        };
+        assert_eq!(
+            resolution.lookup(value).unwrap().name,
+            function.body.vars[0].names[0]
+        );
+        let err = &resolution.errors()[0];
+        assert_eq!(
+            err.kind,
+            ResolveErrorKind::Shadowing {
+                name: "n".to_string(),
+                shadowed: function.params[0].name.span,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "4:11: warning: 'n' shadows an earlier declaration"
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::io::{self, BufRead};
+
+// where the lexer gets the bytes of a source file from
+pub trait Source {
+    // the byte at `offset`, or None if the source ends before it. Offsets
+    // before the last call to `discard_before` may not be asked for.
+    fn byte_at(&mut self, offset: usize) -> Option<u8>;
+
+    // the bytes from `start` up to `end`, which must all have been read with
+    // `byte_at` and not been discarded
+    fn slice(&self, start: usize, end: usize) -> &[u8];
+
+    // tells the source that the bytes before `offset` will not be asked for
+    // again, so it may free them
+    fn discard_before(&mut self, _offset: usize) {}
+
+    // the error that cut the source short, if any
+    fn take_error(&mut self) -> Option<io::Error> {
+        None
+    }
+}
+
+impl Source for &[u8] {
+    fn byte_at(&mut self, offset: usize) -> Option<u8> {
+        self.get(offset).copied()
+    }
+
+    fn slice(&self, start: usize, end: usize) -> &[u8] {
+        &self[start..end]
+    }
+}
+
+// a source that reads from any BufRead (such as stdin or a pipe) as the lexer
+// needs more bytes, only keeping the bytes that have not been discarded
+pub struct ReaderSource<R> {
+    reader: R,
+
+    // the bytes read but not discarded yet
+    buffer: Vec<u8>,
+
+    // the offset of the first byte in `buffer`
+    buffer_start: usize,
+
+    // the offset before which bytes may be dropped from `buffer`
+    discard_offset: usize,
+
+    // whether the reader has run out of bytes
+    eof: bool,
+
+    // the error that stopped the reader, if any
+    error: Option<io::Error>,
+}
+
+impl<R: BufRead> ReaderSource<R> {
+    pub fn new(reader: R) -> Self {
+        ReaderSource {
+            reader,
+            buffer: Vec::new(),
+            buffer_start: 0,
+            discard_offset: 0,
+            eof: false,
+            error: None,
+        }
+    }
+
+    // the number of bytes currently held in memory
+    pub fn buffered_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    // reads the next chunk from the reader into the buffer, returning false
+    // once the reader has been exhausted
+    fn fill(&mut self) -> bool {
+        if self.eof {
+            return false;
+        }
+
+        // only move the remaining bytes to the front once at least half of the
+        // buffer can be dropped, so that discarding is cheap on average
+        let droppable = self.discard_offset - self.buffer_start;
+        if droppable > 0 && droppable >= self.buffer.len() / 2 {
+            self.buffer.drain(..droppable);
+            self.buffer_start = self.discard_offset;
+        }
+
+        loop {
+            match self.reader.fill_buf() {
+                Ok([]) => {
+                    self.eof = true;
+                    return false;
+                }
+                Ok(chunk) => {
+                    let length = chunk.len();
+                    self.buffer.extend_from_slice(chunk);
+                    self.reader.consume(length);
+                    return true;
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    self.error = Some(err);
+                    self.eof = true;
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Source for ReaderSource<R> {
+    fn byte_at(&mut self, offset: usize) -> Option<u8> {
+        assert!(offset >= self.buffer_start);
+
+        while offset >= self.buffer_start + self.buffer.len() {
+            if !self.fill() {
+                return None;
+            }
+        }
+        Some(self.buffer[offset - self.buffer_start])
+    }
+
+    fn slice(&self, start: usize, end: usize) -> &[u8] {
+        &self.buffer[start - self.buffer_start..end - self.buffer_start]
+    }
+
+    fn discard_before(&mut self, offset: usize) {
+        self.discard_offset = self.discard_offset.max(offset);
+    }
+
+    fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    #[test]
+    fn test_reader_source_reads_all_bytes() {
+        let bytes = "source demo\nbegin relax end".as_bytes();
+        let mut source = ReaderSource::new(BufReader::with_capacity(3, bytes));
+
+        let read: Vec<u8> = (0..).map_while(|offset| source.byte_at(offset)).collect();
+        assert_eq!(read, bytes);
+        assert_eq!(source.slice(7, 11), b"demo");
+    }
+
+    #[test]
+    fn test_reader_source_memory_is_bounded() {
+        let bytes = "relax; ".repeat(100_000);
+        let mut source = ReaderSource::new(BufReader::with_capacity(64, bytes.as_bytes()));
+
+        for offset in 0..bytes.len() {
+            assert_eq!(source.byte_at(offset), Some(bytes.as_bytes()[offset]));
+            source.discard_before(offset);
+            assert!(source.buffered_len() <= 256);
+        }
+        assert_eq!(source.byte_at(bytes.len()), None);
+    }
+}
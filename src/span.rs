@@ -0,0 +1,305 @@
+use crate::error::SourcePosition;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+// a range of byte offsets into the source file, `end` being exclusive
+pub struct Span {
+    // the offset of the first byte
+    pub start: usize,
+
+    // the offset one past the last byte
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        assert!(start <= end);
+        Span { start, end }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    // the text of the source file covered by the span
+    pub fn text<'s>(&self, source: &'s str) -> &'s str {
+        &source[self.start..self.end]
+    }
+
+    // the smallest span covering both `self` and `other`
+    pub fn to(&self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+// an index of where each line in a source file starts, used to turn byte
+// offsets into line and column numbers on demand. A line ends with "\n",
+// "\r\n" or a lone "\r". It keeps growing with the source, even when the
+// lexer reads from a stream and frees the bytes themselves as it goes.
+pub struct SourceMap {
+    // the byte offset of the first byte of every line, in increasing order
+    line_starts: Vec<usize>,
+
+    // the offset and width in bytes of every character that is more than one
+    // byte long, in increasing order of offset
+    multibyte_chars: Vec<(usize, usize)>,
+
+    // the offset of every tab, in increasing order
+    tabs: Vec<usize>,
+
+    // the number of columns between tab stops
+    tab_width: usize,
+}
+
+// the number of columns between tab stops unless configured otherwise
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap {
+            line_starts: vec![0],
+            multibyte_chars: Vec::new(),
+            tabs: Vec::new(),
+            tab_width: DEFAULT_TAB_WIDTH,
+        }
+    }
+
+    // sets the number of columns between tab stops, which must not be 0. A
+    // width of 1 makes a tab count as a single column.
+    pub fn with_tab_width(mut self, tab_width: usize) -> Self {
+        assert!(tab_width > 0);
+        self.tab_width = tab_width;
+        self
+    }
+
+    pub fn from_source(source: &[u8]) -> Self {
+        let mut source_map = SourceMap::new();
+        for (index, ch) in source.iter().enumerate() {
+            if *ch == b'\n' || (*ch == b'\r' && source.get(index + 1) != Some(&b'\n')) {
+                source_map.add_line_start(index + 1);
+            } else if *ch == b'\t' {
+                source_map.add_tab(index);
+            } else if let Some(width) = multibyte_char_width(&source[index..]) {
+                source_map.add_multibyte_char(index, width);
+            }
+        }
+        source_map
+    }
+
+    // records that a new line starts at `offset`, which must be past every
+    // line start recorded so far
+    pub fn add_line_start(&mut self, offset: usize) {
+        assert!(offset > *self.line_starts.last().unwrap());
+        self.line_starts.push(offset);
+    }
+
+    // records that a character `width` bytes long starts at `offset`, which
+    // must be past every multibyte character recorded so far
+    pub fn add_multibyte_char(&mut self, offset: usize, width: usize) {
+        assert!(self
+            .multibyte_chars
+            .last()
+            .is_none_or(|(last, _)| offset > *last));
+        self.multibyte_chars.push((offset, width));
+    }
+
+    // records that there is a tab at `offset`, which must be past every tab
+    // recorded so far
+    pub fn add_tab(&mut self, offset: usize) {
+        assert!(self.tabs.last().is_none_or(|last| offset > *last));
+        self.tabs.push(offset);
+    }
+
+    // forgets the lines, multibyte characters and tabs recorded past `offset`, for
+    // when the lexer rewinds to it
+    pub(crate) fn truncate(&mut self, offset: usize) {
+        let lines = self.line_starts.partition_point(|&start| start <= offset);
+        self.line_starts.truncate(lines);
+
+        let chars = self
+            .multibyte_chars
+            .partition_point(|(start, _)| *start < offset);
+        self.multibyte_chars.truncate(chars);
+
+        let tabs = self.tabs.partition_point(|start| *start < offset);
+        self.tabs.truncate(tabs);
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    // the line and column (both starting at 1) of the byte at `offset`, where
+    // the column counts characters rather than bytes and a tab moves it to the
+    // next tab stop, as an editor would display it
+    pub fn position(&self, offset: usize) -> SourcePosition {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+
+        let first = self.tabs.partition_point(|start| *start < line_start);
+        let last = self.tabs.partition_point(|start| *start < offset);
+
+        let mut col = 0;
+        let mut from = line_start;
+        for &tab in &self.tabs[first..last] {
+            col += self.char_count(from, tab);
+            col = (col / self.tab_width + 1) * self.tab_width;
+            from = tab + 1;
+        }
+        col += self.char_count(from, offset);
+
+        SourcePosition { line, col: col + 1 }
+    }
+
+    // the line and column (both starting at 1) of the byte at `offset`, where
+    // the column counts UTF-16 code units as LSP clients do by default, so a
+    // tab is a single unit and a character outside the BMP is two
+    pub fn utf16_position(&self, offset: usize) -> SourcePosition {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+
+        let first = self
+            .multibyte_chars
+            .partition_point(|(start, _)| *start < line_start);
+        let last = self
+            .multibyte_chars
+            .partition_point(|(start, _)| *start < offset);
+        let surrogate_pairs = self.multibyte_chars[first..last]
+            .iter()
+            .filter(|(_, width)| *width == 4)
+            .count();
+
+        SourcePosition {
+            line,
+            col: self.char_count(line_start, offset) + surrogate_pairs + 1,
+        }
+    }
+
+    // the number of characters from `start` up to `end`
+    fn char_count(&self, start: usize, end: usize) -> usize {
+        let first = self
+            .multibyte_chars
+            .partition_point(|(offset, _)| *offset < start);
+        let last = self
+            .multibyte_chars
+            .partition_point(|(offset, _)| *offset < end);
+        let extra_bytes: usize = self.multibyte_chars[first..last]
+            .iter()
+            .map(|(_, width)| width - 1)
+            .sum();
+
+        end - start - extra_bytes
+    }
+}
+
+impl Default for SourceMap {
+    fn default() -> Self {
+        SourceMap::new()
+    }
+}
+
+// the width of the character more than one byte long at the front of
+// `bytes`, or None if they do not start with a whole, valid one. Invalid and
+// truncated sequences are left out of the source map, so that each of their
+// bytes counts as a column.
+pub(crate) fn multibyte_char_width(bytes: &[u8]) -> Option<usize> {
+    let width = utf8_char_width(*bytes.first()?);
+    let valid = width > 1 && bytes.len() >= width && std::str::from_utf8(&bytes[..width]).is_ok();
+    valid.then_some(width)
+}
+
+// the number of bytes in the UTF-8 encoding of a character starting with
+// `lead`, or 0 if `lead` cannot start a character
+pub(crate) fn utf8_char_width(lead: u8) -> usize {
+    match lead {
+        0x00..=0x7F => 1,
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_map_positions() {
+        let source_map = SourceMap::from_source("if\n  then\n\nend".as_bytes());
+
+        assert_eq!(source_map.line_count(), 4);
+        assert_eq!(source_map.position(0), SourcePosition { line: 1, col: 1 });
+        assert_eq!(source_map.position(2), SourcePosition { line: 1, col: 3 });
+        assert_eq!(source_map.position(5), SourcePosition { line: 2, col: 3 });
+        assert_eq!(source_map.position(10), SourcePosition { line: 3, col: 1 });
+        assert_eq!(source_map.position(11), SourcePosition { line: 4, col: 1 });
+    }
+
+    #[test]
+    fn test_source_map_multibyte_columns() {
+        let source_map = SourceMap::from_source("é x\n😀y".as_bytes());
+
+        assert_eq!(source_map.position(3), SourcePosition { line: 1, col: 3 });
+        assert_eq!(source_map.position(9), SourcePosition { line: 2, col: 2 });
+    }
+
+    #[test]
+    fn test_source_map_invalid_utf8_columns() {
+        // an invalid lead byte, a truncated character and a stray continuation
+        // byte each count as a column
+        let source_map = SourceMap::from_source(b"{ \xC0 } \xF0# \xE9x\x80y");
+
+        assert_eq!(source_map.position(6), SourcePosition { line: 1, col: 7 });
+        assert_eq!(source_map.position(7), SourcePosition { line: 1, col: 8 });
+        assert_eq!(source_map.position(13), SourcePosition { line: 1, col: 14 });
+    }
+
+    #[test]
+    fn test_source_map_tab_columns() {
+        let source = "\tif\n  \té\tx".as_bytes();
+
+        let source_map = SourceMap::from_source(source);
+        assert_eq!(source_map.position(1), SourcePosition { line: 1, col: 5 });
+        assert_eq!(source_map.position(7), SourcePosition { line: 2, col: 5 });
+        assert_eq!(source_map.position(10), SourcePosition { line: 2, col: 9 });
+
+        let source_map = SourceMap::from_source(source).with_tab_width(1);
+        assert_eq!(source_map.position(10), SourcePosition { line: 2, col: 6 });
+    }
+
+    #[test]
+    fn test_source_map_line_endings() {
+        let source_map = SourceMap::from_source("a\r\nb\rc\n\r\nd".as_bytes());
+
+        assert_eq!(source_map.line_count(), 5);
+        assert_eq!(source_map.position(1), SourcePosition { line: 1, col: 2 });
+        assert_eq!(source_map.position(3), SourcePosition { line: 2, col: 1 });
+        assert_eq!(source_map.position(5), SourcePosition { line: 3, col: 1 });
+        assert_eq!(source_map.position(9), SourcePosition { line: 5, col: 1 });
+    }
+
+    #[test]
+    fn test_source_map_utf16_columns() {
+        let source_map = SourceMap::from_source("\té😀x\n😀".as_bytes());
+
+        assert_eq!(source_map.position(7), SourcePosition { line: 1, col: 7 });
+        assert_eq!(
+            source_map.utf16_position(7),
+            SourcePosition { line: 1, col: 5 }
+        );
+        assert_eq!(
+            source_map.utf16_position(13),
+            SourcePosition { line: 2, col: 3 }
+        );
+    }
+
+    #[test]
+    fn test_span_to() {
+        assert_eq!(Span::new(4, 6).to(Span::new(1, 2)), Span::new(1, 6));
+    }
+}
@@ -1,39 +1,83 @@
 use std::fmt;
 
-#[derive(Debug, PartialEq)]
-pub enum Token {
+use crate::intern::Symbol;
+use crate::span::Span;
+
+// defines TokenKind together with its keyword table, so that the two cannot
+// drift apart. A variant written as `Kind = "text"` is the keyword `text`.
+macro_rules! token_kinds {
+    ($($kind:ident $(= $text:literal)?,)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        // the kind of a token, without its value
+        pub enum TokenKind {
+            $($kind,)*
+        }
+
+        impl TokenKind {
+            // every token kind, in declaration order
+            pub const ALL: &'static [TokenKind] = &[$(TokenKind::$kind,)*];
+
+            // the spelling of a keyword kind, or None for any other kind
+            pub fn keyword_text(self) -> Option<&'static str> {
+                match self {
+                    $($(TokenKind::$kind => Some($text),)?)*
+                    _ => None,
+                }
+            }
+        }
+
+        // the keyword kind spelled `lexeme`, or None if it is not a keyword.
+        // The match compiles to a comparison on the length and the bytes of
+        // the lexeme, which is faster than searching a table.
+        pub fn keyword(lexeme: &str) -> Option<TokenKind> {
+            match lexeme {
+                $($($text => Some(TokenKind::$kind),)?)*
+                _ => None,
+            }
+        }
+
+        // every reserved keyword and its TokenKind, in declaration order
+        pub const RESERVED_WORDS: &[(&str, TokenKind)] = &[$($(($text, TokenKind::$kind),)?)*];
+    };
+}
+
+token_kinds! {
     // end-of-file
     Eof,
+    // a lexical error, only produced in recovery mode
+    Error,
     // identifier
-    Id(String),
+    Id,
     // number literal
-    Number(i32),
+    Number,
     // string literal
-    StringLiteral(String),
+    StringLiteral,
+    // a "{! ... }" documentation comment
+    DocComment,
 
     // keywords
-    Array,
-    Begin,
-    Boolean,
-    Call,
-    Do,
-    Else,
-    Elsif,
-    End,
-    False,
-    Function,
-    Get,
-    If,
-    Integer,
-    Leave,
-    Not,
-    Put,
-    Relax,
-    Source,
-    Then,
-    To,
-    True,
-    While,
+    Array = "array",
+    Begin = "begin",
+    Boolean = "boolean",
+    Call = "call",
+    Do = "do",
+    Else = "else",
+    Elsif = "elsif",
+    End = "end",
+    False = "false",
+    Function = "function",
+    Get = "get",
+    If = "if",
+    Integer = "integer",
+    Leave = "leave",
+    Not = "not",
+    Put = "put",
+    Relax = "relax",
+    Source = "source",
+    Then = "then",
+    To = "to",
+    True = "true",
+    While = "while",
 
     // relational operators
     Equal,
@@ -45,14 +89,14 @@ pub enum Token {
 
     // additive operators
     Minus,
-    Or,
+    Or = "or",
     Plus,
 
     // multiplicative operators
-    And,
+    And = "and",
     Divide,
     Multiply,
-    Remainder,
+    Remainder = "rem",
 
     // other non-alphabetic operators
     CloseBracket,
@@ -65,74 +109,179 @@ pub enum Token {
     Semicolon,
 }
 
-impl fmt::Display for Token {
+impl TokenKind {
+    // the fixed spelling of a keyword or operator kind, or None for the kinds
+    // whose text varies
+    pub fn text(self) -> Option<&'static str> {
+        match self {
+            TokenKind::Equal => Some("="),
+            TokenKind::GreaterEqual => Some(">="),
+            TokenKind::GreaterThan => Some(">"),
+            TokenKind::LessEqual => Some("<="),
+            TokenKind::LessThan => Some("<"),
+            TokenKind::NotEqual => Some("<>"),
+            TokenKind::Minus => Some("-"),
+            TokenKind::Plus => Some("+"),
+            TokenKind::Divide => Some("/"),
+            TokenKind::Multiply => Some("*"),
+            TokenKind::CloseBracket => Some("]"),
+            TokenKind::CloseParenthesis => Some(")"),
+            TokenKind::Comma => Some(","),
+            TokenKind::Concatenate => Some("."),
+            TokenKind::Gets => Some(":="),
+            TokenKind::OpenBracket => Some("["),
+            TokenKind::OpenParenthesis => Some("("),
+            TokenKind::Semicolon => Some(";"),
+            _ => self.keyword_text(),
+        }
+    }
+}
+
+impl fmt::Display for TokenKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?}", self)
     }
 }
 
-// the maximum length of an identifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+// the value carried by identifier, number and string literal tokens
+pub enum TokenValue {
+    // the token has no value
+    None,
+    // the name of an identifier, the (decoded) value of a string literal or
+    // the text of a doc comment
+    Symbol(Symbol),
+    // the value of a number literal, which is never negative
+    Number(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+// a token produced by the lexer
+pub struct Token {
+    pub kind: TokenKind,
+
+    // where the token is in the source file
+    pub span: Span,
+
+    pub value: TokenValue,
+}
+
+impl Token {
+    // a token without a value
+    pub fn new(kind: TokenKind, span: Span) -> Self {
+        Token {
+            kind,
+            span,
+            value: TokenValue::None,
+        }
+    }
+
+    // the symbol of an identifier, string literal or doc comment token
+    pub fn symbol(&self) -> Option<Symbol> {
+        match self.value {
+            TokenValue::Symbol(symbol) => Some(symbol),
+            _ => None,
+        }
+    }
+
+    // the value of a number token
+    pub fn number(&self) -> Option<u64> {
+        match self.value {
+            TokenValue::Number(number) => Some(number),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Token {
+    fn default() -> Self {
+        Token::new(TokenKind::Eof, Span::default())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+// the kinds of source text between tokens
+pub enum TriviaKind {
+    // a run of whitespace other than newlines
+    Whitespace,
+    // a single "\n", "\r\n" or "\r"
+    Newline,
+    // a '{' comment, including any nested comments, or a "//" comment up to
+    // the end of its line
+    Comment,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+// a piece of source text between tokens that does not affect the meaning of
+// the program
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub span: Span,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+// a token together with its source text and the trivia around it
+pub struct LosslessToken {
+    pub token: Token,
+
+    // the source text of the token, empty for TokenKind::Eof
+    pub text: String,
+
+    // the trivia before the token
+    pub leading: Vec<Trivia>,
+
+    // the trivia after the token, up to and including the end of its line
+    pub trailing: Vec<Trivia>,
+}
+
+// writes the token exactly as it appears in the source, trivia included
+impl fmt::Display for LosslessToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for trivia in &self.leading {
+            write!(f, "{}", trivia.text)?;
+        }
+        write!(f, "{}", self.text)?;
+        for trivia in &self.trailing {
+            write!(f, "{}", trivia.text)?;
+        }
+        Ok(())
+    }
+}
+
+// the maximum length of an identifier unless configured otherwise, see
+// LexerOptions::max_id_length
 pub const MAX_ID_LENGTH: usize = 32;
 
-// an array of reserved keywords and its corresponding Token. The array should
-// be sorted since Lexer::process_word performs binary search on the array.
-pub const RESERVED_WORDS: &[(&str, Token)] = &[
-    ("and", Token::And),
-    ("array", Token::Array),
-    ("begin", Token::Begin),
-    ("boolean", Token::Boolean),
-    ("call", Token::Call),
-    ("do", Token::Do),
-    ("else", Token::Else),
-    ("elsif", Token::Elsif),
-    ("end", Token::End),
-    ("false", Token::False),
-    ("function", Token::Function),
-    ("get", Token::Get),
-    ("if", Token::If),
-    ("integer", Token::Integer),
-    ("leave", Token::Leave),
-    ("not", Token::Not),
-    ("or", Token::Or),
-    ("put", Token::Put),
-    ("relax", Token::Relax),
-    ("rem", Token::Remainder),
-    ("source", Token::Source),
-    ("then", Token::Then),
-    ("to", Token::To),
-    ("true", Token::True),
-    ("while", Token::While),
-];
-
-pub fn create_token_from_reserved_words_index(index: usize) -> Token {
-    assert!(index < RESERVED_WORDS.len());
-
-    match RESERVED_WORDS[index].1 {
-        Token::And => Token::And,
-        Token::Array => Token::Array,
-        Token::Begin => Token::Begin,
-        Token::Boolean => Token::Boolean,
-        Token::Call => Token::Call,
-        Token::Do => Token::Do,
-        Token::Else => Token::Else,
-        Token::Elsif => Token::Elsif,
-        Token::End => Token::End,
-        Token::False => Token::False,
-        Token::Function => Token::Function,
-        Token::Get => Token::Get,
-        Token::If => Token::If,
-        Token::Integer => Token::Integer,
-        Token::Leave => Token::Leave,
-        Token::Not => Token::Not,
-        Token::Or => Token::Or,
-        Token::Put => Token::Put,
-        Token::Relax => Token::Relax,
-        Token::Remainder => Token::Remainder,
-        Token::Source => Token::Source,
-        Token::Then => Token::Then,
-        Token::To => Token::To,
-        Token::True => Token::True,
-        Token::While => Token::While,
-        _ => unreachable!(),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyword_table_matches_token_kinds() {
+        let keyword_kinds: Vec<TokenKind> = TokenKind::ALL
+            .iter()
+            .copied()
+            .filter(|kind| kind.keyword_text().is_some())
+            .collect();
+        assert_eq!(keyword_kinds.len(), RESERVED_WORDS.len());
+
+        for &(text, kind) in RESERVED_WORDS {
+            assert_eq!(keyword(text), Some(kind), "{}", text);
+            assert_eq!(kind.keyword_text(), Some(text), "{}", kind);
+
+            // the lexer only looks up lexemes that could be identifiers
+            assert!(text.bytes().all(|b| b.is_ascii_lowercase()), "{}", text);
+            assert!(text.len() <= MAX_ID_LENGTH, "{}", text);
+        }
+
+        // no two keywords share a spelling
+        let mut texts: Vec<&str> = RESERVED_WORDS.iter().map(|(text, _)| *text).collect();
+        texts.sort_unstable();
+        texts.dedup();
+        assert_eq!(texts.len(), RESERVED_WORDS.len());
+
+        assert_eq!(keyword("While"), None);
+        assert_eq!(keyword("whilst"), None);
     }
 }
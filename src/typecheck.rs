@@ -0,0 +1,805 @@
+use crate::ast::{self, AssignValue, BinaryOp, Ident, Program, Stmt, UnaryOp};
+use crate::error::{TypeError, TypeErrorKind};
+use crate::intern::Interner;
+use crate::resolve::{DeclId, DeclKind, Resolution, Resolver};
+use crate::span::{SourceMap, Span};
+use crate::typed::{self, ExprKind, Ty, Var};
+
+// The type checker resolves the names of a program and checks that:
+//
+// - "and", "or" and "not" apply to booleans, and arithmetic operators,
+//   "rem" and the ordering comparisons to integers
+// - '=' and '<>' compare values of the same type
+// - arrays are indexed, and given a length, with integers
+// - calls have as many arguments as the function has parameters, each of
+//   the type of its parameter, and only functions returning a value are
+//   called in expressions
+// - "leave" has a value of the return type in a function returning one, and
+//   none elsewhere
+// - a function returning a value leaves on every path through its body
+// - the conditions of "if", "elsif" and "while" are booleans
+// - strings and '.' only appear as the items of a "put" statement, each
+//   item being an integer, boolean or string
+//
+// It then hands on the program as a typed AST, see typed.rs.
+pub struct TypeChecker<'a> {
+    interner: &'a Interner,
+    source_map: &'a SourceMap,
+    file_name: Option<String>,
+
+    resolution: Resolution,
+
+    // the return type of the function being checked, None for a procedure
+    // or the program body
+    return_type: Option<Ty>,
+
+    // whether the AST has error nodes from parsing in recovery mode, whose
+    // errors have been reported by the parser
+    has_error_nodes: bool,
+
+    // the errors (and warnings) reported so far
+    errors: Vec<TypeError>,
+}
+
+impl<'a> TypeChecker<'a> {
+    // a type checker for a program parsed into `interner`, whose source is
+    // described by `source_map`
+    pub fn new(interner: &'a Interner, source_map: &'a SourceMap) -> Self {
+        TypeChecker {
+            interner,
+            source_map,
+            file_name: None,
+            resolution: Resolution::default(),
+            return_type: None,
+            has_error_nodes: false,
+            errors: Vec::new(),
+        }
+    }
+
+    // sets the file name reported in errors
+    pub fn with_file_name(mut self, file_name: &str) -> Self {
+        self.file_name = Some(file_name.to_string());
+        self
+    }
+
+    // checks the whole program, reporting all errors (see `errors`) rather
+    // than stopping at the first. The typed program is only handed on if
+    // there are no errors other than warnings.
+    pub fn check(&mut self, program: &Program) -> Option<typed::Program> {
+        let mut resolver = Resolver::new(self.interner, self.source_map);
+        if let Some(file_name) = &self.file_name {
+            resolver = resolver.with_file_name(file_name);
+        }
+        self.resolution = resolver.resolve(program);
+        self.errors = self
+            .resolution
+            .errors()
+            .iter()
+            .cloned()
+            .map(TypeError::from)
+            .collect();
+
+        let typed = self.check_program(program);
+        self.errors.sort_by_key(|err| err.span.start);
+        let has_errors = self.errors.iter().any(|err| !err.kind.is_warning());
+        (!has_errors && !self.has_error_nodes).then_some(typed)
+    }
+
+    // the errors and warnings, in the order they appear in the source once
+    // the program has been checked
+    pub fn errors(&self) -> &[TypeError] {
+        &self.errors
+    }
+
+    // the declarations of the program checked and what its names refer to
+    pub fn resolution(&self) -> &Resolution {
+        &self.resolution
+    }
+
+    fn check_program(&mut self, program: &Program) -> typed::Program {
+        let functions = program
+            .functions
+            .iter()
+            .map(|function| self.check_function(function))
+            .collect();
+        self.return_type = None;
+        let body = self.check_body(&program.body);
+
+        typed::Program {
+            name: program.name,
+            functions,
+            body,
+            span: program.span,
+        }
+    }
+
+    fn check_function(&mut self, function: &ast::FunctionDecl) -> typed::Function {
+        let params = function
+            .params
+            .iter()
+            .map(|param| self.declared_var(param.name, param.ty))
+            .collect();
+        self.return_type = function.return_type.map(Ty::from);
+        let body = self.check_body(&function.body);
+        if self.return_type.is_some() && !always_leaves(&function.body.statements) {
+            let name_text = self.interner.resolve(function.name.symbol).to_string();
+            self.error(TypeErrorKind::MissingLeave(name_text), function.name.span);
+        }
+
+        typed::Function {
+            decl: self.resolution.binding(function.name).unwrap(),
+            name: function.name,
+            params,
+            return_type: self.return_type,
+            body,
+            span: function.span,
+        }
+    }
+
+    fn check_body(&mut self, body: &ast::Body) -> typed::Body {
+        let vars = body
+            .vars
+            .iter()
+            .flat_map(|var| var.names.iter().map(move |name| (*name, var.ty)))
+            .map(|(name, ty)| self.declared_var(name, ty))
+            .collect();
+
+        typed::Body {
+            vars,
+            statements: self.check_statements(&body.statements),
+            span: body.span,
+        }
+    }
+
+    fn check_statements(&mut self, statements: &[Stmt]) -> Vec<typed::Stmt> {
+        statements
+            .iter()
+            .filter_map(|stmt| self.check_stmt(stmt))
+            .collect()
+    }
+
+    // the typed statement, or None if a name in it could not be resolved
+    // (or it is an error node), in which case there are errors anyway
+    fn check_stmt(&mut self, stmt: &Stmt) -> Option<typed::Stmt> {
+        match stmt {
+            Stmt::Assign {
+                target,
+                index,
+                value,
+                span,
+            } => {
+                let target = self.var(*target);
+                let index = index.as_ref().map(|index| self.check_index(index));
+                let place = self.place_type(target, index.is_some());
+                match value {
+                    AssignValue::Expr(value) => {
+                        let value = self.check_expr(value);
+                        self.expect(&value, place);
+                        Some(typed::Stmt::Assign {
+                            target: target?,
+                            index,
+                            value,
+                            span: *span,
+                        })
+                    }
+                    AssignValue::Array {
+                        length,
+                        span: array_span,
+                    } => {
+                        let length = self.check_index(length);
+                        if !matches!(place, Ty::Array(_) | Ty::Error) {
+                            self.error(TypeErrorKind::NotAnArray(place), *array_span);
+                        }
+                        Some(typed::Stmt::NewArray {
+                            target: target?,
+                            length,
+                            span: *span,
+                        })
+                    }
+                }
+            }
+            Stmt::Call { name, args, span } => {
+                let (function, args, _) = self.check_call(*name, args, *span);
+                Some(typed::Stmt::Call {
+                    function: function?,
+                    name: *name,
+                    args,
+                    span: *span,
+                })
+            }
+            Stmt::If {
+                branches,
+                else_branch,
+                span,
+            } => {
+                let branches = branches
+                    .iter()
+                    .map(|branch| typed::IfBranch {
+                        condition: self.check_condition(&branch.condition),
+                        body: self.check_statements(&branch.body),
+                        span: branch.span,
+                    })
+                    .collect();
+                let else_branch = else_branch
+                    .as_ref()
+                    .map(|else_branch| self.check_statements(else_branch));
+                Some(typed::Stmt::If {
+                    branches,
+                    else_branch,
+                    span: *span,
+                })
+            }
+            Stmt::Get {
+                target,
+                index,
+                span,
+            } => {
+                let target_ident = *target;
+                let target = self.var(target_ident);
+                let index = index.as_ref().map(|index| self.check_index(index));
+                let place = self.place_type(target, index.is_some());
+                if !matches!(place, Ty::Boolean | Ty::Integer | Ty::Error) {
+                    self.error(TypeErrorKind::NotScalar(place), target_ident.span);
+                }
+                Some(typed::Stmt::Get {
+                    target: target?,
+                    index,
+                    span: *span,
+                })
+            }
+            Stmt::Leave { value, span } => {
+                let value = value.as_ref().map(|value| self.check_expr(value));
+                match (self.return_type, &value) {
+                    (Some(ty), Some(value)) => self.expect(value, ty),
+                    (Some(ty), None) => self.error(TypeErrorKind::MissingValue(ty), *span),
+                    (None, Some(value)) => self.error(TypeErrorKind::UnexpectedValue, value.span),
+                    (None, None) => {}
+                }
+                Some(typed::Stmt::Leave { value, span: *span })
+            }
+            Stmt::Put { value, span } => {
+                let mut items = Vec::new();
+                self.check_put_items(value, &mut items);
+                Some(typed::Stmt::Put { items, span: *span })
+            }
+            Stmt::Relax { span } => Some(typed::Stmt::Relax { span: *span }),
+            Stmt::While {
+                condition,
+                body,
+                span,
+            } => Some(typed::Stmt::While {
+                condition: self.check_condition(condition),
+                body: self.check_statements(body),
+                span: *span,
+            }),
+            Stmt::Error { .. } => {
+                self.has_error_nodes = true;
+                None
+            }
+        }
+    }
+
+    // adds the items of a "put" statement, which are joined by '.', to
+    // `items`
+    fn check_put_items(&mut self, expr: &ast::Expr, items: &mut Vec<typed::Expr>) {
+        match expr {
+            ast::Expr::Binary {
+                op: BinaryOp::Concatenate,
+                left,
+                right,
+                ..
+            } => {
+                self.check_put_items(left, items);
+                self.check_put_items(right, items);
+            }
+            ast::Expr::String { value, span } => items.push(typed::Expr {
+                kind: ExprKind::String(*value),
+                ty: Ty::String,
+                span: *span,
+            }),
+            expr => {
+                let item = self.check_expr(expr);
+                if let Ty::Array(_) = item.ty {
+                    self.error(TypeErrorKind::NotScalar(item.ty), item.span);
+                }
+                items.push(item);
+            }
+        }
+    }
+
+    fn check_expr(&mut self, expr: &ast::Expr) -> typed::Expr {
+        let span = expr.span();
+        let (kind, ty) = match expr {
+            ast::Expr::Variable(name) => match self.var(*name) {
+                Some(var) => (ExprKind::Variable(var), var.ty),
+                None => (ExprKind::Error, Ty::Error),
+            },
+            ast::Expr::Number { value, .. } => (ExprKind::Number(*value), Ty::Integer),
+            ast::Expr::Boolean { value, .. } => (ExprKind::Boolean(*value), Ty::Boolean),
+            ast::Expr::String { value, .. } => {
+                self.error(TypeErrorKind::OnlyInPut, span);
+                (ExprKind::String(*value), Ty::Error)
+            }
+            ast::Expr::Unary { op, operand, .. } => {
+                let ty = match op {
+                    UnaryOp::Negate => Ty::Integer,
+                    UnaryOp::Not => Ty::Boolean,
+                };
+                let operand = self.check_expr(operand);
+                self.expect(&operand, ty);
+                let kind = ExprKind::Unary {
+                    op: *op,
+                    operand: Box::new(operand),
+                };
+                (kind, ty)
+            }
+            ast::Expr::Binary {
+                op: BinaryOp::Concatenate,
+                ..
+            } => {
+                // the items are checked as in "put", so that the strings in
+                // them are not reported as well
+                self.error(TypeErrorKind::OnlyInPut, span);
+                self.check_put_items(expr, &mut Vec::new());
+                (ExprKind::Error, Ty::Error)
+            }
+            ast::Expr::Binary {
+                op, left, right, ..
+            } => {
+                let left = self.check_expr(left);
+                let right = self.check_expr(right);
+                let ty = self.check_binary(*op, &left, &right, span);
+                let kind = ExprKind::Binary {
+                    op: *op,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                };
+                (kind, ty)
+            }
+            ast::Expr::Index { array, index, .. } => {
+                let array = self.var(*array);
+                let index = self.check_index(index);
+                let ty = self.place_type(array, true);
+                match array {
+                    Some(array) => {
+                        let kind = ExprKind::Index {
+                            array,
+                            index: Box::new(index),
+                        };
+                        (kind, ty)
+                    }
+                    None => (ExprKind::Error, Ty::Error),
+                }
+            }
+            ast::Expr::Call { name, args, .. } => {
+                let (function, args, return_type) = self.check_call(*name, args, span);
+                let ty = match (function, return_type) {
+                    (Some(_), Some(ty)) => ty,
+                    (Some(_), None) => {
+                        let name_text = self.interner.resolve(name.symbol).to_string();
+                        self.error(TypeErrorKind::NoValue(name_text), span);
+                        Ty::Error
+                    }
+                    (None, _) => Ty::Error,
+                };
+                match function {
+                    Some(function) => {
+                        let kind = ExprKind::Call {
+                            function,
+                            name: *name,
+                            args,
+                        };
+                        (kind, ty)
+                    }
+                    None => (ExprKind::Error, Ty::Error),
+                }
+            }
+            ast::Expr::Error { .. } => {
+                self.has_error_nodes = true;
+                (ExprKind::Error, Ty::Error)
+            }
+        };
+
+        typed::Expr { kind, ty, span }
+    }
+
+    // the type of a binary operation, other than a concatenation
+    fn check_binary(
+        &mut self,
+        op: BinaryOp,
+        left: &typed::Expr,
+        right: &typed::Expr,
+        span: Span,
+    ) -> Ty {
+        match op {
+            BinaryOp::And | BinaryOp::Or => {
+                self.expect(left, Ty::Boolean);
+                self.expect(right, Ty::Boolean);
+                Ty::Boolean
+            }
+            BinaryOp::Add
+            | BinaryOp::Subtract
+            | BinaryOp::Multiply
+            | BinaryOp::Divide
+            | BinaryOp::Remainder => {
+                self.expect(left, Ty::Integer);
+                self.expect(right, Ty::Integer);
+                Ty::Integer
+            }
+            BinaryOp::GreaterEqual
+            | BinaryOp::GreaterThan
+            | BinaryOp::LessEqual
+            | BinaryOp::LessThan => {
+                self.expect(left, Ty::Integer);
+                self.expect(right, Ty::Integer);
+                Ty::Boolean
+            }
+            BinaryOp::Equal | BinaryOp::NotEqual => {
+                if left.ty != right.ty && left.ty != Ty::Error && right.ty != Ty::Error {
+                    let kind = TypeErrorKind::Incomparable {
+                        left: left.ty,
+                        right: right.ty,
+                    };
+                    self.error(kind, span);
+                }
+                Ty::Boolean
+            }
+            BinaryOp::Concatenate => unreachable!("concatenation is checked as in 'put'"),
+        }
+    }
+
+    // checks the arguments of a call against the parameters of the function
+    // called, returning the function (if the name is one) and its return type
+    fn check_call(
+        &mut self,
+        name: Ident,
+        args: &[ast::Expr],
+        span: Span,
+    ) -> (Option<DeclId>, Vec<typed::Expr>, Option<Ty>) {
+        let args: Vec<typed::Expr> = args.iter().map(|arg| self.check_expr(arg)).collect();
+        let function = self.resolution.binding(name);
+        let (params, return_type) = match function.map(|id| &self.resolution.declaration(id).kind) {
+            Some(DeclKind::Function {
+                params,
+                return_type,
+            }) => (params.clone(), return_type.map(Ty::from)),
+            // the resolver has reported the name
+            _ => return (None, args, None),
+        };
+
+        if params.len() != args.len() {
+            let kind = TypeErrorKind::WrongArgumentCount {
+                expected: params.len(),
+                found: args.len(),
+            };
+            self.error(kind, span);
+        }
+        for (arg, param) in args.iter().zip(params) {
+            self.expect(arg, param.into());
+        }
+        (function, args, return_type)
+    }
+
+    fn check_condition(&mut self, condition: &ast::Expr) -> typed::Expr {
+        let condition = self.check_expr(condition);
+        self.expect(&condition, Ty::Boolean);
+        condition
+    }
+
+    // an array index or length, which must be an integer
+    fn check_index(&mut self, index: &ast::Expr) -> typed::Expr {
+        let index = self.check_expr(index);
+        self.expect(&index, Ty::Integer);
+        index
+    }
+
+    // the type of what is assigned to, or read, through a variable, being an
+    // element of it if `indexed`
+    fn place_type(&mut self, var: Option<Var>, indexed: bool) -> Ty {
+        let ty = var.map_or(Ty::Error, |var| var.ty);
+        if !indexed {
+            return ty;
+        }
+        match ty {
+            Ty::Array(base) => base.into(),
+            Ty::Error => Ty::Error,
+            ty => {
+                self.error(TypeErrorKind::NotAnArray(ty), var.unwrap().name.span);
+                Ty::Error
+            }
+        }
+    }
+
+    // a parameter or variable where it is declared
+    fn declared_var(&self, name: Ident, ty: ast::Type) -> Var {
+        Var {
+            decl: self.resolution.binding(name).unwrap(),
+            name,
+            ty: ty.into(),
+        }
+    }
+
+    // a use of a parameter or variable, None if the name is not one, which
+    // the resolver has reported
+    fn var(&self, name: Ident) -> Option<Var> {
+        let decl = self.resolution.binding(name)?;
+        Some(Var {
+            decl,
+            name,
+            ty: self.resolution.declaration(decl).ty()?.into(),
+        })
+    }
+
+    // reports an expression that is not of type `ty`
+    fn expect(&mut self, expr: &typed::Expr, ty: Ty) {
+        if expr.ty != ty && expr.ty != Ty::Error && ty != Ty::Error {
+            let kind = TypeErrorKind::Mismatch {
+                expected: ty,
+                found: expr.ty,
+            };
+            self.error(kind, expr.span);
+        }
+    }
+
+    fn error(&mut self, kind: TypeErrorKind, span: Span) {
+        self.errors.push(TypeError {
+            kind,
+            span,
+            position: self.source_map.position(span.start),
+            file_name: self.file_name.clone(),
+        });
+    }
+}
+
+// whether running `statements` always ends in a "leave", which is the case
+// if one of them always does, since only a "leave" skips the ones after it.
+// An "if" has to have an "else", and a "while" may not run its body at all.
+// A statement with a syntax error counts as leaving, so as not to report an
+// error that fixing it could make go away.
+fn always_leaves(statements: &[Stmt]) -> bool {
+    statements.iter().any(|stmt| match stmt {
+        Stmt::Leave { .. } | Stmt::Error { .. } => true,
+        Stmt::If {
+            branches,
+            else_branch: Some(else_branch),
+            ..
+        } => {
+            branches.iter().all(|branch| always_leaves(&branch.body)) && always_leaves(else_branch)
+        }
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::BaseType;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn check(source: &str) -> (Option<typed::Program>, Vec<String>) {
+        let mut parser = Parser::new(Lexer::new(source.as_bytes()));
+        let program = parser.parse_program().unwrap();
+        let mut checker = TypeChecker::new(parser.interner(), parser.source_map());
+        let typed = checker.check(&program);
+        let errors = checker
+            .errors()
+            .iter()
+            .map(|err| format!("{}: {}", err.span.text(source), err.kind))
+            .collect();
+        (typed, errors)
+    }
+
+    // the errors of a program body with the given variables and statements
+    fn errors_in(body: &str) -> Vec<String> {
+        let source = format!(
+            "source e
+function f(integer n, boolean b) to integer begin leave n end
+function p() begin relax end
+begin
+  integer i; boolean b; integer array a; boolean array flags;
+  {}
+end",
+            body
+        );
+        let (typed, errors) = check(&source);
+        assert_eq!(typed.is_none(), !errors.is_empty());
+        errors
+    }
+
+    #[test]
+    fn test_check_program() {
+        let source = "source demo
+function sum(integer array a, integer n) to integer
+begin
+  integer i, total;
+  i := 0; total := 0;
+  while i < n do total := total + a[i]; i := i + 1 end;
+  leave total
+end
+begin
+  integer array a; integer n; boolean big;
+  get n; a := array n; get a[0];
+  big := (sum(a, n) > 100) or not (a[0] = n);
+  if big then put \"big: \" . sum(a, n) . \" \" . big else relax end
+end";
+        let (typed, errors) = check(source);
+        assert_eq!(errors, Vec::<String>::new());
+        let typed = typed.unwrap();
+
+        let sum = &typed.functions[0];
+        assert_eq!(sum.return_type, Some(Ty::Integer));
+        assert_eq!(sum.params[0].ty, Ty::Array(BaseType::Integer));
+        assert_eq!(typed.body.vars.len(), 3);
+
+        let statements = &typed.body.statements;
+        assert!(matches!(statements[1], typed::Stmt::NewArray { .. }));
+        match &statements[3] {
+            typed::Stmt::Assign { target, value, .. } => {
+                assert_eq!(target.ty, Ty::Boolean);
+                assert_eq!(value.ty, Ty::Boolean);
+                match &value.kind {
+                    ExprKind::Binary { left, .. } => match &left.kind {
+                        ExprKind::Binary { left, .. } => {
+                            assert_eq!(left.ty, Ty::Integer);
+                            assert!(
+                                matches!(left.kind, ExprKind::Call { function, .. } if function == sum.decl)
+                            );
+                        }
+                        kind => panic!("expected a comparison, found {:?}", kind),
+                    },
+                    kind => panic!("expected an 'or', found {:?}", kind),
+                }
+            }
+            stmt => panic!("expected an assignment, found {:?}", stmt),
+        }
+        match &statements[4] {
+            typed::Stmt::If { branches, .. } => match &branches[0].body[0] {
+                typed::Stmt::Put { items, .. } => {
+                    let types: Vec<Ty> = items.iter().map(|item| item.ty).collect();
+                    assert_eq!(types, [Ty::String, Ty::Integer, Ty::String, Ty::Boolean]);
+                }
+                stmt => panic!("expected a put statement, found {:?}", stmt),
+            },
+            stmt => panic!("expected an if statement, found {:?}", stmt),
+        }
+    }
+
+    #[test]
+    fn test_operator_types() {
+        assert_eq!(
+            errors_in(
+                "b := i and b; i := -b + 1; b := not i; b := i < b; b := b = i; b := a <> flags"
+            ),
+            [
+                "i: expected boolean, but found integer",
+                "b: expected integer, but found boolean",
+                "i: expected boolean, but found integer",
+                "b: expected integer, but found boolean",
+                "b = i: cannot compare boolean with integer",
+                "a <> flags: cannot compare integer array with boolean array",
+            ]
+        );
+        assert_eq!(
+            errors_in("b := (i = 1) and (b <> true); i := i rem 2 * (i / 3 - 4)"),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_array_types() {
+        assert_eq!(
+            errors_in("a[b] := 1; i := i[0]; flags[0] := 1; a := array true; i := array 3; a := flags; get a; put a"),
+            [
+                "b: expected integer, but found boolean",
+                "i: expected an array, but found integer",
+                "1: expected boolean, but found integer",
+                "true: expected integer, but found boolean",
+                "array 3: expected an array, but found integer",
+                "flags: expected integer array, but found boolean array",
+                "a: expected integer or boolean, but found integer array",
+                "a: expected integer or boolean, but found integer array",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_call_types() {
+        assert_eq!(
+            errors_in("i := f(1); i := f(b, i); b := f(1, true); call p(1); i := p()"),
+            [
+                "f(1): expected 2 arguments, but found 1",
+                "b: expected integer, but found boolean",
+                "i: expected boolean, but found integer",
+                "f(1, true): expected boolean, but found integer",
+                "call p(1): expected 0 arguments, but found 1",
+                "p(): 'p' does not return a value",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_statement_types() {
+        assert_eq!(
+            errors_in("if i then relax elsif b then relax end; while a do relax end; leave 1"),
+            [
+                "i: expected boolean, but found integer",
+                "a: expected boolean, but found integer array",
+                "1: 'leave' can only have a value in a function returning one",
+            ]
+        );
+        let (_, errors) = check(
+            "source e
+function f() to boolean begin if true then leave else leave 1 end end
+begin relax end",
+        );
+        assert_eq!(
+            errors,
+            [
+                "leave: 'leave' needs a value of type boolean",
+                "1: expected boolean, but found integer",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_functions_leave_on_every_path() {
+        let (_, errors) = check(
+            "source e
+function f() to integer begin relax end
+function g(boolean b) to integer begin if b then leave 1 end end
+function h(boolean b) to integer begin if b then leave 1 elsif b then relax else leave 2 end end
+function w(boolean b) to integer begin while b do leave 1 end end
+function ok(boolean b) to integer
+begin
+  while b do relax end;
+  if b then leave 1 elsif not b then if b then leave 2 else leave 3 end else leave 4 end;
+  b := not b
+end
+function p() begin relax end
+begin relax end",
+        );
+        assert_eq!(
+            errors,
+            [
+                "f: function 'f' can end without leaving a value",
+                "g: function 'g' can end without leaving a value",
+                "h: function 'h' can end without leaving a value",
+                "w: function 'w' can end without leaving a value",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strings_only_in_put() {
+        assert_eq!(
+            errors_in("put \"i = \" . i . (\"!\" . b); i := \"x\"; b := (\"a\" . \"b\") = \"ab\"; i := f(\"n\", b)"),
+            [
+                "\"x\": strings and '.' are only allowed in 'put'",
+                "\"a\" . \"b\": strings and '.' are only allowed in 'put'",
+                "\"ab\": strings and '.' are only allowed in 'put'",
+                "\"n\": strings and '.' are only allowed in 'put'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_errors_are_reported() {
+        let (typed, errors) = check("source e begin integer i, i; j := i + k end");
+        assert!(typed.is_none());
+        assert_eq!(
+            errors,
+            [
+                "i: 'i' is already declared",
+                "j: 'j' is not declared",
+                "k: 'k' is not declared",
+            ]
+        );
+
+        // warnings do not stop the program being handed on
+        let (typed, errors) =
+            check("source e function f() begin integer f; f := 1 end begin call f() end");
+        assert!(typed.is_some());
+        assert_eq!(errors, ["f: 'f' shadows an earlier declaration"]);
+    }
+}
@@ -0,0 +1,185 @@
+use std::fmt;
+
+use crate::ast::{BaseType, BinaryOp, Ident, Type, UnaryOp};
+use crate::intern::Symbol;
+use crate::resolve::DeclId;
+use crate::span::Span;
+
+// The typed AST is what the type checker hands on to the backend: the program
+// with every name bound to its declaration and every expression annotated
+// with its type. Unlike the AST it has no error nodes, and the items of a
+// "put" statement are listed rather than joined by concatenation.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+// the type of an expression
+pub enum Ty {
+    Boolean,
+    Integer,
+    Array(BaseType),
+    // a string literal, which can only be an item of a "put" statement
+    String,
+    // the type of an expression with an error, which matches every type so
+    // that an error is not reported again for the expressions around it
+    Error,
+}
+
+impl From<BaseType> for Ty {
+    fn from(base: BaseType) -> Self {
+        match base {
+            BaseType::Boolean => Ty::Boolean,
+            BaseType::Integer => Ty::Integer,
+        }
+    }
+}
+
+impl From<Type> for Ty {
+    fn from(ty: Type) -> Self {
+        if ty.is_array {
+            Ty::Array(ty.base)
+        } else {
+            ty.base.into()
+        }
+    }
+}
+
+// writes the type as it is declared, such as "integer array"
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ty::Boolean => write!(f, "boolean"),
+            Ty::Integer => write!(f, "integer"),
+            Ty::Array(base) => write!(f, "{} array", Ty::from(*base)),
+            Ty::String => write!(f, "string"),
+            Ty::Error => write!(f, "<error>"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub name: Ident,
+    pub functions: Vec<Function>,
+    pub body: Body,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub decl: DeclId,
+    pub name: Ident,
+    pub params: Vec<Var>,
+
+    // None for a procedure
+    pub return_type: Option<Ty>,
+
+    pub body: Body,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+// a parameter or variable, where it is declared or used
+pub struct Var {
+    pub decl: DeclId,
+    pub name: Ident,
+    pub ty: Ty,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Body {
+    pub vars: Vec<Var>,
+    pub statements: Vec<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    // assigns to a variable, or to an element of an array if `index` is set
+    Assign {
+        target: Var,
+        index: Option<Expr>,
+        value: Expr,
+        span: Span,
+    },
+    // assigns a new array of `length` elements to an array variable
+    NewArray {
+        target: Var,
+        length: Expr,
+        span: Span,
+    },
+    Call {
+        function: DeclId,
+        name: Ident,
+        args: Vec<Expr>,
+        span: Span,
+    },
+    If {
+        branches: Vec<IfBranch>,
+        else_branch: Option<Vec<Stmt>>,
+        span: Span,
+    },
+    Get {
+        target: Var,
+        index: Option<Expr>,
+        span: Span,
+    },
+    Leave {
+        value: Option<Expr>,
+        span: Span,
+    },
+    // writes each item, which is an integer, boolean or string, in turn
+    Put {
+        items: Vec<Expr>,
+        span: Span,
+    },
+    Relax {
+        span: Span,
+    },
+    While {
+        condition: Expr,
+        body: Vec<Stmt>,
+        span: Span,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IfBranch {
+    pub condition: Expr,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub ty: Ty,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Variable(Var),
+    Number(u64),
+    Boolean(bool),
+    String(Symbol),
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    Index {
+        array: Var,
+        index: Box<Expr>,
+    },
+    Call {
+        function: DeclId,
+        name: Ident,
+        args: Vec<Expr>,
+    },
+    // an expression with an error, which is never part of a program the
+    // type checker hands on
+    Error,
+}
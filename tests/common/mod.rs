@@ -0,0 +1,40 @@
+// helpers shared by the integration tests, each of which only uses some
+#![allow(dead_code)]
+
+use std::{fs, io};
+use svlang::ast::Program;
+
+// the names of the files in the directory at `path`, sorted
+pub fn get_test_files(path: &str) -> io::Result<Vec<String>> {
+    let entries = fs::read_dir(path)?;
+
+    let mut file_names: Vec<String> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.is_file() {
+                path.file_name()?.to_str().map(|s| s.to_owned())
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    file_names.sort();
+
+    Ok(file_names)
+}
+
+// the debug form of an AST with its spans left out, since printing a program
+// moves its nodes around
+pub fn without_spans(program: &Program) -> String {
+    let debug = format!("{:?}", program);
+    let mut out = String::new();
+    let mut rest = debug.as_str();
+    while let Some(start) = rest.find("Span {") {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        rest = &rest[rest.find('}').unwrap() + 1..];
+    }
+    out.push_str(rest);
+    out
+}
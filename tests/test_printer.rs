@@ -0,0 +1,62 @@
+mod common;
+
+use common::{get_test_files, without_spans};
+use std::fs;
+use svlang::ast::printer::print_program;
+use svlang::ast::Program;
+use svlang::error::ParseError;
+use svlang::intern::Interner;
+use svlang::lexer::Lexer;
+use svlang::parser::Parser;
+
+fn parse(source: &str, interner: Interner) -> Result<(Program, Interner), ParseError> {
+    let mut parser = Parser::new(Lexer::new(source.as_bytes()).with_interner(interner));
+    let program = parser.parse_program()?;
+    Ok((program, parser.into_interner()))
+}
+
+// checks that parse(print(parse(source))) == parse(source) for every file in
+// `tests_dir` that parses, returning how many did
+fn round_trip_files(tests_dir: &str) -> usize {
+    let mut count = 0;
+    for file in get_test_files(tests_dir).unwrap() {
+        let source_file = format!("{}/{}", tests_dir, file);
+        let source = fs::read_to_string(&source_file)
+            .unwrap_or_else(|_| panic!("Could not read the file: {}", source_file));
+
+        let Ok((program, interner)) = parse(&source, Interner::new()) else {
+            continue;
+        };
+
+        println!("-- START --");
+        println!("{}", file);
+
+        let printed = print_program(&program, &interner);
+        let (reparsed, _) = parse(&printed, interner)
+            .unwrap_or_else(|err| panic!("{}\nin the printed source:\n{}", err, printed));
+        assert_eq!(
+            without_spans(&reparsed),
+            without_spans(&program),
+            "printed source of {}:\n{}",
+            file,
+            printed
+        );
+        count += 1;
+    }
+    count
+}
+
+#[test]
+fn test_printer_round_trip() {
+    // most of the lexer tests are not whole programs, or have errors on
+    // purpose, so only the ones that parse can be printed
+    let count = round_trip_files("tests/resources/lexer");
+    assert!(count > 0, "no file in the lexer tests is a whole program");
+}
+
+#[test]
+fn test_printer_round_trip_programs() {
+    let tests_dir = "tests/resources/printer";
+    let count = round_trip_files(tests_dir);
+    assert_eq!(count, get_test_files(tests_dir).unwrap().len());
+}